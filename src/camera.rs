@@ -0,0 +1,47 @@
+use rltk::Point;
+
+use crate::map::Map;
+
+/// Part of the screen the map is drawn in, below the log and the stats
+pub const VIEW_X: i32 = 0;
+pub const VIEW_Y: i32 = 11;
+pub const VIEW_WIDTH: i32 = 80;
+pub const VIEW_HEIGHT: i32 = 39;
+
+/// Translates between map and screen coordinates, the top left corner of the viewport shows
+/// the map tile at (min_x, min_y)
+#[derive(Copy, Clone)]
+pub struct Camera {
+    min_x: i32,
+    min_y: i32
+}
+
+impl Camera {
+    /// Centers the viewport on a map position. It does not scroll past the edges of the map,
+    /// a map smaller than the viewport is drawn in its top left corner
+    pub fn centered_on(map: &Map, center: Point) -> Camera {
+        let min_x = (center.x - VIEW_WIDTH / 2).clamp(0, i32::max(0, map.width - VIEW_WIDTH));
+        let min_y = (center.y - VIEW_HEIGHT / 2).clamp(0, i32::max(0, map.height - VIEW_HEIGHT));
+        Camera{min_x, min_y}
+    }
+
+    /// Screen position of a map tile, None when it is out of the viewport
+    pub fn map_to_screen(&self, x: i32, y: i32) -> Option<Point> {
+        let (view_x, view_y) = (x - self.min_x, y - self.min_y);
+        if !is_in_view(view_x, view_y) { return None }
+        Some(Point::new(view_x + VIEW_X, view_y + VIEW_Y))
+    }
+
+    /// Map tile at a screen position, None when it is out of the viewport or of the map
+    pub fn screen_to_map(&self, map: &Map, (x, y): (i32, i32)) -> Option<Point> {
+        let (view_x, view_y) = (x - VIEW_X, y - VIEW_Y);
+        if !is_in_view(view_x, view_y) { return None }
+        let (map_x, map_y) = (view_x + self.min_x, view_y + self.min_y);
+        if map_x >= map.width || map_y >= map.height { return None }
+        Some(Point::new(map_x, map_y))
+    }
+}
+
+fn is_in_view(view_x: i32, view_y: i32) -> bool {
+    (0..VIEW_WIDTH).contains(&view_x) && (0..VIEW_HEIGHT).contains(&view_y)
+}
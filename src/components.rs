@@ -1,8 +1,8 @@
 use serde::{Serialize, Deserialize};
 use hecs::*;
-use rltk;
 
 use crate::RenderOrder;
+use crate::map::Map;
 
 #[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Position {
@@ -10,7 +10,7 @@ pub struct Position {
     pub y: i32,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Renderable {
     pub glyph: rltk::FontCharType,
     pub fg: rltk::RGB,
@@ -33,29 +33,67 @@ impl Default for Renderable {
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OtherLevelPosition {
+    pub x: i32,
+    pub y: i32,
+    pub depth: i32
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Player {}
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Viewshed {
     pub visible_tiles: Vec<rltk::Point>,
     pub range: i32,
     pub dirty: bool
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Monster {}
 
+/// Someone peaceful wandering around, the player does not attack them
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Npc {}
+
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Name {
     pub name: String
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct BlocksTile {}
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+/// Blocks movement and sight while it is closed
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Door {
+    pub open: bool
+}
+
+impl Door {
+    pub fn open(world: &mut World, map: &mut Map, door: Entity) {
+        if let Ok(mut door) = world.get_mut::<Door>(door) {
+            door.open = true;
+        }
+        if let Ok(mut render) = world.get_mut::<Renderable>(door) {
+            render.glyph = rltk::to_cp437('/');
+        }
+        if let Ok(pos) = world.get::<Position>(door) {
+            let idx = map.xy_idx(pos.x, pos.y);
+            map.blocked[idx] = false;
+            map.view_blocked[idx] = false;
+        }
+
+        // Whoever could see the door may now see through it
+        for (_id, vs) in world.query_mut::<&mut Viewshed>() {
+            vs.dirty = true;
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct CombatStats {
     pub max_hp: i32,
     pub hp: i32,
@@ -83,54 +121,62 @@ pub struct WantsToUnequipItem {
     pub item: Entity
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Item {}
 
-#[derive(PartialEq, Copy, Clone)]
+#[derive(PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub enum EquipmentSlot { RightHand, LeftHand }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Serialize, Deserialize)]
 pub struct Equippable {
     pub slot: EquipmentSlot
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Equipped {
     pub owner: Entity,
     pub slot: EquipmentSlot
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct InBackpack {
     pub owner: Entity
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Consumable {}
 
+#[derive(Serialize, Deserialize)]
 pub struct MeleePowerBonus {
     pub power: i32
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct MeleeDefenseBonus {
     pub defense: i32
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Serialize, Deserialize)]
 pub struct ProvidesHealing {
     pub heal: i32
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Ranged {
     pub range: i32
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Serialize, Deserialize)]
 pub struct DealsDamage {
     pub damage: i32
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Serialize, Deserialize)]
 pub struct Confusion {
     pub turns: i32
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct AreaOfEffect {
     pub radius: i32
 }
@@ -141,11 +187,12 @@ pub struct WantsToUseItem {
 }
 
 pub struct TakeDamage {
-    pub amount: Vec<i32>
+    pub amount: Vec<i32>,
+    pub last_source: String
 }
 
 impl TakeDamage {
-    pub fn add_damage(world: &mut World, victim: Entity, amount: i32) {
+    pub fn add_damage(world: &mut World, victim: Entity, amount: i32, source: &str) {
         let mut needs_take_damage = false;
 
         {
@@ -153,6 +200,7 @@ impl TakeDamage {
             match take_damage {
                 Ok(mut take_dmg) => {
                     take_dmg.amount.push(amount);
+                    take_dmg.last_source = source.to_string();
                 },
                 Err(_e) => {
                     needs_take_damage = true;
@@ -161,11 +209,12 @@ impl TakeDamage {
         }
 
         if needs_take_damage {
-            let _res = world.insert_one(victim, TakeDamage{amount: vec![amount]});
+            let _res = world.insert_one(victim, TakeDamage{amount: vec![amount], last_source: source.to_string()});
         }
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct SerializeMe {}
 
 pub struct Lifetime {
@@ -1,7 +1,7 @@
 use hecs::*;
 use resources::*;
-use crate::RunState;
-use crate::components::{TakeDamage, CombatStats, Player, Name};
+use crate::{RunState, RunStats};
+use crate::components::{TakeDamage, CombatStats, Player, Monster, Name};
 use crate::gamelog::GameLog;
 
 pub fn damage(world: &mut World) {
@@ -16,6 +16,7 @@ pub fn damage(world: &mut World) {
 
 pub fn delete_the_dead(world: &mut World, res: &mut Resources) {
     let mut log = res.get_mut::<GameLog>().unwrap();
+    let mut run_stats = res.get_mut::<RunStats>().unwrap();
     let mut dead: Vec<Entity> = vec![];
 
     for (id, stats) in &mut world.query::<&CombatStats>() {
@@ -25,6 +26,9 @@ pub fn delete_the_dead(world: &mut World, res: &mut Resources) {
             match player {
                 Err(_) => {
                     dead.push(id);
+                    if world.get::<Monster>(id).is_ok() {
+                        run_stats.monsters_killed += 1;
+                    }
                     if let Ok(name) = name {
                         log.messages.push(format!("{} is dead", &name.name));
                     }
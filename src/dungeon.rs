@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+
+use hecs::*;
+use serde::{Serialize, Deserialize};
+
+use crate::components::{OtherLevelPosition, Player, Position, SerializeMe, Viewshed};
+use crate::map::Map;
+
+
+/// Every level visited by the player, keyed by depth
+#[derive(Default, Serialize, Deserialize, Clone)]
+pub struct Dungeon {
+    maps: HashMap<i32, Map>
+}
+
+impl Dungeon {
+    pub fn store_map(&mut self, map: &Map) {
+        self.maps.insert(map.depth, map.clone());
+    }
+
+    pub fn get_map(&self, depth: i32) -> Option<Map> {
+        self.maps.get(&depth).map(|map| {
+            let mut map = map.clone();
+            map.reset_content();
+            map
+        })
+    }
+}
+
+/// Moves every entity of the level out of the map by replacing its Position with an
+/// OtherLevelPosition. Entities that are not saved (particles) are deleted instead
+pub fn freeze_level_entities(world: &mut World, depth: i32) {
+    let mut to_freeze: Vec<(Entity, Position)> = Vec::new();
+    let mut to_delete: Vec<Entity> = Vec::new();
+
+    for (id, (pos, serialize)) in world.query::<(&Position, Option<&SerializeMe>)>().without::<Player>().iter() {
+        match serialize {
+            Some(_) => to_freeze.push((id, *pos)),
+            None => to_delete.push(id)
+        }
+    }
+
+    for (id, pos) in to_freeze {
+        world.remove_one::<Position>(id).unwrap();
+        world.insert_one(id, OtherLevelPosition{x: pos.x, y: pos.y, depth}).unwrap();
+    }
+
+    for id in to_delete {
+        world.despawn(id).unwrap();
+    }
+}
+
+/// Brings back the entities that were frozen on the level at this depth
+pub fn thaw_level_entities(world: &mut World, depth: i32) {
+    let to_thaw: Vec<(Entity, OtherLevelPosition)> = world.query::<&OtherLevelPosition>().iter()
+        .filter(|(_id, pos)| pos.depth == depth)
+        .map(|(id, pos)| (id, *pos))
+        .collect();
+
+    for (id, pos) in to_thaw {
+        world.remove_one::<OtherLevelPosition>(id).unwrap();
+        world.insert_one(id, Position{x: pos.x, y: pos.y}).unwrap();
+        if let Ok(mut vs) = world.get_mut::<Viewshed>(id) {
+            vs.dirty = true;
+        }
+    }
+}
@@ -1,5 +1,6 @@
+use serde::{Serialize, Deserialize};
 
-
+#[derive(Serialize, Deserialize, Clone)]
 pub struct GameLog {
     pub messages: Vec<String>
 }
@@ -5,9 +5,14 @@ use hecs::*;
 use resources::*;
 use crate::components::{CombatStats, Name, Position, InBackpack, Viewshed, Equipped, Equippable};
 use crate::gamelog::GameLog;
-use crate::map;
 use crate::map::Map;
+use crate::camera::{Camera, VIEW_WIDTH};
 use crate::{Palette, RunState};
+use crate::rng::{RunSeed, MAX_SEED};
+use crate::replay::Replay;
+use crate::highscores::HighScoreTable;
+use crate::map_builders::LayoutError;
+use crate::saveload_system::{SaveError, SaveFiles, SaveSlotList, SlotInfo, NB_SAVE_SLOTS};
 
 #[derive(PartialEq, Copy, Clone)]
 pub enum ItemMenuResult {Cancel, NoResponse, Selected}
@@ -16,12 +21,43 @@ pub enum ItemActionSelection {Cancel, NoSelection, Used, Dropped, Unequipped}
 
 #[derive(PartialEq, Eq, Clone, Copy, TryFromPrimitive, IntoPrimitive)]
 #[repr(i8)]
-pub enum MainMenuSelection {NewGame, LoadGame, Exit}
+pub enum MainMenuSelection {Continue, NewGame, Seed, LoadGame, HighScores, Exit}
+
+const NB_MAIN_MENU_SELECTIONS: i8 = 6;
 
 pub enum MainMenuResult {NoSelection {selected: MainMenuSelection}, Selection {selected: MainMenuSelection}}
 
+#[derive(PartialEq, Copy, Clone)]
+pub enum SaveSlotMode {NewGame, LoadGame}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum SlotConfirmation {Overwrite, Delete}
+
+pub enum SaveSlotResult {NoSelection {selected: usize, confirm: Option<SlotConfirmation>}, Cancel, Selected {slot: usize}, Delete {slot: usize}}
+
+pub enum EnterSeedResult {NoResponse, Cancel, Selected {seed: u64}}
+
+/// Digits typed so far on the seed entry screen
+pub struct SeedInput {
+    pub text: String
+}
+
 pub enum GameOverResult {NoSelection, QuitToMenu}
 
+/// Where the files about the last run were written, or why they could not be. Shown on the game
+/// over screen and in the main menu so players can find them
+pub struct RunReport {
+    pub messages: Vec<String>
+}
+
+fn draw_run_report(res: &Resources, ctx: &mut Rltk, y: i32) {
+    if let Ok(report) = res.get::<RunReport>() {
+        for (i, message) in report.messages.iter().enumerate() {
+            ctx.print_color_centered(y + i as i32, Palette::COLOR_2, Palette::MAIN_BG, message);
+        }
+    }
+}
+
 pub fn draw_gui(world: &World, res: &Resources, ctx: &mut Rltk) {
     ctx.print_color(0, 10, Palette::MAIN_FG, Palette::MAIN_BG, "─".repeat(80));
 
@@ -30,7 +66,14 @@ pub fn draw_gui(world: &World, res: &Resources, ctx: &mut Rltk) {
     let hp_gui = format!("{} / {} HP", player_stats.hp, player_stats.max_hp);
     let map = res.get::<Map>().unwrap();
 
+    let seed = res.get::<RunSeed>().unwrap();
+
+    ctx.print_color(62, 8, Palette::MAIN_FG, Palette::MAIN_BG, format!("Seed: {}", seed.0));
     ctx.print_color(62, 9, Palette::MAIN_FG, Palette::MAIN_BG, format!("Depth: {}", map.depth));
+    let player_pos = res.get::<Point>().unwrap();
+    if let Some(theme) = map.room_theme(player_pos.x, player_pos.y) {
+        ctx.print_color(62, 7, Palette::COLOR_4, Palette::MAIN_BG, theme.name());
+    }
     ctx.print_color(62, 1, Palette::MAIN_FG, Palette::MAIN_BG, hp_gui);
 
     for y in 0..10 {
@@ -39,34 +82,38 @@ pub fn draw_gui(world: &World, res: &Resources, ctx: &mut Rltk) {
     ctx.print_color(60, 10, Palette::MAIN_FG, Palette::MAIN_BG, "┴");
 
     let log = res.get::<GameLog>().unwrap();
-    let mut y = 1;
-    for m in log.messages.iter().rev() {
-        if y < 9 {
-            ctx.print_color(2, y, Palette::MAIN_FG, Palette::MAIN_BG, m);
-        }
-        y += 1;
+    for (y, m) in log.messages.iter().rev().take(8).enumerate() {
+        ctx.print_color(2, y + 1, Palette::MAIN_FG, Palette::MAIN_BG, m);
     }
 
     let mouse_pos = ctx.mouse_pos();
     ctx.set_bg(mouse_pos.0, mouse_pos.1, Palette::COLOR_3);
-    draw_tooltips(&world, &res, ctx);
+    draw_tooltips(world, res, ctx);
+
+    if let Ok(replay) = res.get::<Replay>() {
+        let status = if replay.paused { "paused".to_string() } else { format!("{}/s", replay.speed) };
+        ctx.print_color(2, 9, Palette::COLOR_4, Palette::MAIN_BG, format!("Replay {}/{} {} (space, +, -, esc)", replay.next, replay.recording.commands.len(), status));
+    }
 
     // Display fps
-    ctx.print_color(78, 49, Palette::MAIN_FG, Palette::MAIN_BG, &format!("{}", ctx.fps));
+    ctx.print_color(78, 49, Palette::MAIN_FG, Palette::MAIN_BG, format!("{}", ctx.fps));
 }
 
 pub fn draw_tooltips(world: &World, res: &Resources, ctx: &mut Rltk) {
     let map = res.get::<Map>().unwrap();
+    let camera = Camera::centered_on(&map, *res.get::<Point>().unwrap());
 
     let mouse_pos = ctx.mouse_pos();
-    let map_mouse_pos = map.transform_mouse_pos(mouse_pos);
-    if !map.mouse_in_bounds(map_mouse_pos) { return; }
+    let map_mouse_pos = match camera.screen_to_map(&map, mouse_pos) {
+        Some(map_mouse_pos) => map_mouse_pos,
+        None => return
+    };
 
     let mut tooltip: Vec<String> = Vec::new();
 
     for (_id, (name, pos)) in world.query::<(&Name, &Position)>().iter() {
         let idx = map.xy_idx(pos.x, pos.y);
-        if pos.x == map_mouse_pos.0 && pos.y == map_mouse_pos.1 && map.visible_tiles[idx] {
+        if pos.x == map_mouse_pos.x && pos.y == map_mouse_pos.y && map.visible_tiles[idx] {
             tooltip.push(name.name.to_string());
         }
     }
@@ -81,8 +128,8 @@ pub fn draw_tooltips(world: &World, res: &Resources, ctx: &mut Rltk) {
         let mut sign = 1;
         let mut arrow_pos = Point::new(mouse_pos.0 + 1, mouse_pos.1);
         let mut left_x = mouse_pos.0 + 4;
-        let mut y = mouse_pos.1;
-        if mouse_pos.0 > map.width / 2 {
+        let y = mouse_pos.1;
+        if mouse_pos.0 > VIEW_WIDTH / 2 {
             sign = -1;
             arrow_pos = Point::new(mouse_pos.0 - 2, mouse_pos.1);
             left_x = mouse_pos.0 - width;
@@ -91,14 +138,25 @@ pub fn draw_tooltips(world: &World, res: &Resources, ctx: &mut Rltk) {
         if sign == -1 {ctx.fill_region(rltk::Rect{x1: left_x, x2: left_x - 3 + width, y1: y, y2: y + tooltip.len() as i32 - 1}, rltk::to_cp437(' '), Palette::MAIN_FG, Palette::COLOR_3);}
         else {ctx.fill_region(rltk::Rect{x1: left_x - 1, x2: left_x + width - 4, y1: y, y2: y + tooltip.len() as i32 - 1}, rltk::to_cp437(' '), Palette::MAIN_FG, Palette::COLOR_3);}
 
-        for s in tooltip.iter() {
-            ctx.print_color(left_x, y, Palette::MAIN_FG, Palette::COLOR_3, s);
-            y += 1;
+        for (i, s) in tooltip.iter().enumerate() {
+            ctx.print_color(left_x, y + i as i32, Palette::MAIN_FG, Palette::COLOR_3, s);
         }
         ctx.print_color(arrow_pos.x, arrow_pos.y, Palette::MAIN_FG, Palette::COLOR_3, "->");
     }
 }
 
+/// Items of the inventory in the order they are listed, backpack first then equipment
+pub fn inventory_items(world: &World, player_id: Entity) -> Vec<Entity> {
+    let mut items: Vec<Entity> = Vec::new();
+    for (id, (pack, _name)) in world.query::<(&InBackpack, &Name)>().iter() {
+        if pack.owner == player_id { items.push(id); }
+    }
+    for (id, (equipped, _name)) in world.query::<(&Equipped, &Name)>().iter() {
+        if equipped.owner == player_id { items.push(id); }
+    }
+    items
+}
+
 pub fn show_inventory(world: &mut World, res: &mut Resources, ctx: &mut Rltk) -> (ItemMenuResult, Option<Entity>) {
     let player_id = res.get::<Entity>().unwrap();
 
@@ -106,7 +164,7 @@ pub fn show_inventory(world: &mut World, res: &mut Resources, ctx: &mut Rltk) ->
     let mut query = world.query::<(&InBackpack, &Name)>();
     let inventory = query.iter().filter(|item| item.1.0.owner == *player_id);
     let backpack_count = inventory.count();
-    let mut y = 25 - (backpack_count / 2);
+    let y = 25 - (backpack_count / 2);
     ctx.draw_box(10, y - 2, 31, backpack_count + 3, Palette::MAIN_FG, Palette::MAIN_BG);
 
     let title = "Inventory";
@@ -114,13 +172,12 @@ pub fn show_inventory(world: &mut World, res: &mut Resources, ctx: &mut Rltk) ->
 
     let mut useable: Vec<Entity> = Vec::new();
     for (j, (id, (_pack, name))) in world.query::<(&InBackpack, &Name)>().iter().filter(|item| item.1.0.owner == *player_id).enumerate() {
-        ctx.set(12, y, Palette::MAIN_FG, Palette::MAIN_BG, rltk::to_cp437('('));
-        ctx.set(13, y, Palette::COLOR_0, Palette::MAIN_BG, 97 + j as rltk::FontCharType);
-        ctx.set(14, y, Palette::MAIN_FG, Palette::MAIN_BG, rltk::to_cp437(')'));
+        ctx.set(12, y + j, Palette::MAIN_FG, Palette::MAIN_BG, rltk::to_cp437('('));
+        ctx.set(13, y + j, Palette::COLOR_0, Palette::MAIN_BG, 97 + j as rltk::FontCharType);
+        ctx.set(14, y + j, Palette::MAIN_FG, Palette::MAIN_BG, rltk::to_cp437(')'));
 
-        ctx.print_color(16, y, Palette::MAIN_FG, Palette::MAIN_BG, &name.name.to_string());
+        ctx.print_color(16, y + j, Palette::MAIN_FG, Palette::MAIN_BG, &name.name);
         useable.push(id);
-        y += 1;
     }
 
     // Items equipped
@@ -128,7 +185,7 @@ pub fn show_inventory(world: &mut World, res: &mut Resources, ctx: &mut Rltk) ->
     let equipped_items = query.iter().filter(|item| item.1.0.owner == *player_id);
     let equipped_count = equipped_items.count();
     
-    let mut y = 25 - (equipped_count / 2);
+    let y = 25 - (equipped_count / 2);
     ctx.draw_box(45, y - 2, 31, equipped_count + 3, Palette::MAIN_FG, Palette::MAIN_BG);
 
     let title = "Equipment";
@@ -137,13 +194,12 @@ pub fn show_inventory(world: &mut World, res: &mut Resources, ctx: &mut Rltk) ->
     let mut equipped: Vec<Entity> = Vec::new();
     for (j, (id, (_pack, name))) in world.query::<(&Equipped, &Name)>().iter().filter(|item| item.1.0.owner == *player_id).enumerate() {
         let offset = j + backpack_count;
-        ctx.set(47, y, Palette::MAIN_FG, Palette::MAIN_BG, rltk::to_cp437('('));
-        ctx.set(48, y, Palette::COLOR_0, Palette::MAIN_BG, 97 + offset as rltk::FontCharType);
-        ctx.set(49, y, Palette::MAIN_FG, Palette::MAIN_BG, rltk::to_cp437(')'));
+        ctx.set(47, y + j, Palette::MAIN_FG, Palette::MAIN_BG, rltk::to_cp437('('));
+        ctx.set(48, y + j, Palette::COLOR_0, Palette::MAIN_BG, 97 + offset as rltk::FontCharType);
+        ctx.set(49, y + j, Palette::MAIN_FG, Palette::MAIN_BG, rltk::to_cp437(')'));
 
-        ctx.print_color(51, y, Palette::MAIN_FG, Palette::MAIN_BG, &name.name.to_string());
+        ctx.print_color(51, y + j, Palette::MAIN_FG, Palette::MAIN_BG, &name.name);
         equipped.push(id);
-        y += 1;
     }
 
     match ctx.key {
@@ -195,7 +251,7 @@ pub fn show_item_actions(world: &mut World, _res: &mut Resources, item: Entity,
 
 
     match ctx.key {
-        None => (ItemActionSelection::NoSelection),
+        None => ItemActionSelection::NoSelection,
         Some(key) => {
             match key {
                 VirtualKeyCode::Escape => { ItemActionSelection::Cancel }
@@ -216,6 +272,8 @@ pub fn show_item_actions(world: &mut World, _res: &mut Resources, item: Entity,
 pub fn ranged_target(world: &mut World, res: &mut Resources, ctx: &mut Rltk, range: i32) -> (ItemMenuResult, Option<Point>) {
     let player_id = res.get::<Entity>().unwrap();
     let player_pos = res.get::<Point>().unwrap();
+    let map = res.get::<Map>().unwrap();
+    let camera = Camera::centered_on(&map, *player_pos);
     ctx.print_color(5, 12, Palette::COLOR_0, Palette::MAIN_BG, "Select a target");
 
     let mut valid_cells: Vec<Point> = Vec::new();
@@ -225,7 +283,9 @@ pub fn ranged_target(world: &mut World, res: &mut Resources, ctx: &mut Rltk, ran
             for pt in player_vs.visible_tiles.iter() {
                 let dist = rltk::DistanceAlg::Pythagoras.distance2d(*player_pos, *pt);
                 if dist as i32 <= range {
-                    ctx.set_bg(pt.x + map::OFFSET_X as i32, pt.y + map::OFFSET_Y as i32, Palette::COLOR_4);
+                    if let Some(screen_pos) = camera.map_to_screen(pt.x, pt.y) {
+                        ctx.set_bg(screen_pos.x, screen_pos.y, Palette::COLOR_4);
+                    }
                     valid_cells.push(*pt);
                 }
             }
@@ -233,14 +293,14 @@ pub fn ranged_target(world: &mut World, res: &mut Resources, ctx: &mut Rltk, ran
     }
 
     let mouse_pos = ctx.mouse_pos();
-    let map_mouse_pos = (mouse_pos.0 - map::OFFSET_X as i32, mouse_pos.1 - map::OFFSET_Y as i32);
+    let map_mouse_pos = camera.screen_to_map(&map, mouse_pos);
     let mut valid_target = false;
     for pt in valid_cells.iter() {
-        if pt.x == map_mouse_pos.0 && pt.y == map_mouse_pos.1 { valid_target = true }
+        if Some(*pt) == map_mouse_pos { valid_target = true }
     }
     if valid_target {
         ctx.set_bg(mouse_pos.0, mouse_pos.1, Palette::COLOR_2);
-        if ctx.left_click { return (ItemMenuResult::Selected, Some(Point::new(map_mouse_pos.0, map_mouse_pos.1))) }
+        if ctx.left_click { return (ItemMenuResult::Selected, map_mouse_pos) }
     }
     else {
         ctx.set_bg(mouse_pos.0, mouse_pos.1, Palette::COLOR_1);
@@ -248,30 +308,59 @@ pub fn ranged_target(world: &mut World, res: &mut Resources, ctx: &mut Rltk, ran
     }
 
     match ctx.key {
-        None => (ItemMenuResult::NoResponse, None),
-        Some(key) => {
-            match key {
-                VirtualKeyCode::Escape => { return (ItemMenuResult::Cancel, None) },
-                _ => (ItemMenuResult::NoResponse, None)
-            }
-        }
+        Some(VirtualKeyCode::Escape) => (ItemMenuResult::Cancel, None),
+        _ => (ItemMenuResult::NoResponse, None)
     }
 }
 
 pub fn main_menu(_world: &mut World, res: &mut Resources, ctx: &mut Rltk) -> MainMenuResult {
+    let load_error = res.get::<SaveError>().ok().map(|e| e.to_string());
+    if let Some(message) = load_error {
+        if error_screen(ctx, "Unable to load the game", &message) {
+            res.remove::<SaveError>();
+        }
+        return MainMenuResult::NoSelection{selected: MainMenuSelection::LoadGame};
+    }
+
+    let layout_error = res.get::<LayoutError>().ok().map(|e| e.0.clone());
+    if let Some(message) = layout_error {
+        if error_screen(ctx, "The default dungeon layout is used", &message) {
+            res.remove::<LayoutError>();
+        }
+        let selected = match *res.get::<RunState>().unwrap() {
+            RunState::MainMenu{menu_selection} => menu_selection,
+            _ => MainMenuSelection::NewGame
+        };
+        return MainMenuResult::NoSelection{selected};
+    }
+
     let runstate = res.get::<RunState>().unwrap();
+    let seed = res.get::<RunSeed>().unwrap();
 
     let get_fg = |sel, menu_item| {
-        if sel == menu_item { return Palette::COLOR_1 }
-        else { return Palette::MAIN_FG }
+        if sel == menu_item { Palette::COLOR_1 }
+        else { Palette::MAIN_FG }
     };
 
     ctx.print_color_centered(15, Palette::COLOR_2, Palette::MAIN_BG, "Roguelike");
 
     if let RunState::MainMenu{menu_selection: selection} = *runstate {
-        ctx.print_color_centered(25, get_fg(selection, MainMenuSelection::NewGame), Palette::MAIN_BG, "Begin new adventure");
-        ctx.print_color_centered(30, get_fg(selection, MainMenuSelection::LoadGame), Palette::MAIN_BG, "Load game");
-        ctx.print_color_centered(35, get_fg(selection, MainMenuSelection::Exit), Palette::MAIN_BG, "Exit");
+        let save_files = res.get::<SaveFiles>().unwrap();
+        if save_files.autosave {
+            ctx.print_color_centered(23, get_fg(selection, MainMenuSelection::Continue), Palette::MAIN_BG, "Continue");
+        } else {
+            ctx.print_color_centered(23, Palette::COLOR_2, Palette::MAIN_BG, "Continue");
+        }
+        ctx.print_color_centered(27, get_fg(selection, MainMenuSelection::NewGame), Palette::MAIN_BG, "Begin new adventure");
+        ctx.print_color_centered(31, get_fg(selection, MainMenuSelection::Seed), Palette::MAIN_BG, format!("Seed: {}", seed.0));
+        if save_files.saves {
+            ctx.print_color_centered(35, get_fg(selection, MainMenuSelection::LoadGame), Palette::MAIN_BG, "Load game");
+        } else {
+            ctx.print_color_centered(35, Palette::COLOR_2, Palette::MAIN_BG, "Load game");
+        }
+        ctx.print_color_centered(39, get_fg(selection, MainMenuSelection::HighScores), Palette::MAIN_BG, "High scores");
+        ctx.print_color_centered(43, get_fg(selection, MainMenuSelection::Exit), Palette::MAIN_BG, "Exit");
+        draw_run_report(res, ctx, 46);
 
         match ctx.key {
             None => {return MainMenuResult::NoSelection{selected: selection}}
@@ -280,14 +369,12 @@ pub fn main_menu(_world: &mut World, res: &mut Resources, ctx: &mut Rltk) -> Mai
                     VirtualKeyCode::Escape => {return MainMenuResult::Selection{selected: MainMenuSelection::Exit}}
                     VirtualKeyCode::Up => {
                         let sel: i8 = selection.into();
-                        // TODO: use len of menu selections instead of hard coded 3
-                        let new_sel = MainMenuSelection::try_from((sel - 1i8).rem_euclid(3)).unwrap();
+                        let new_sel = MainMenuSelection::try_from((sel - 1i8).rem_euclid(NB_MAIN_MENU_SELECTIONS)).unwrap();
                         return MainMenuResult::NoSelection{selected: new_sel}
                     }
                     VirtualKeyCode::Down => {
                         let sel: i8 = selection.into();
-                        // TODO: use len of menu selections instead of hard coded 3
-                        let new_sel = MainMenuSelection::try_from((sel + 1i8).rem_euclid(3)).unwrap();
+                        let new_sel = MainMenuSelection::try_from((sel + 1i8).rem_euclid(NB_MAIN_MENU_SELECTIONS)).unwrap();
                         return MainMenuResult::NoSelection{selected: new_sel}
                     }
                     VirtualKeyCode:: Return => {return MainMenuResult::Selection{selected: selection}}
@@ -300,11 +387,222 @@ pub fn main_menu(_world: &mut World, res: &mut Resources, ctx: &mut Rltk) -> Mai
     MainMenuResult::NoSelection{selected: MainMenuSelection::NewGame}
 }
 
-pub fn game_over(ctx: &mut Rltk) -> GameOverResult {
-    ctx.print_color_centered(19, Palette::MAIN_FG, Palette::MAIN_BG, "You are dead.");
-    ctx.print_color_centered(23, Palette::MAIN_FG, Palette::MAIN_BG, "Press any key to return to the main menu.");
+pub fn enter_seed(res: &mut Resources, ctx: &mut Rltk) -> EnterSeedResult {
+    let mut input = res.get_mut::<SeedInput>().unwrap();
+
+    ctx.print_color_centered(15, Palette::COLOR_2, Palette::MAIN_BG, "Roguelike");
+    ctx.print_color_centered(25, Palette::MAIN_FG, Palette::MAIN_BG, "Type the seed of the run");
+    ctx.draw_box(30, 27, 19, 2, Palette::MAIN_FG, Palette::MAIN_BG);
+    ctx.print_color_centered(28, Palette::COLOR_1, Palette::MAIN_BG, format!("{}_", input.text));
+    ctx.print_color_centered(35, Palette::MAIN_FG, Palette::MAIN_BG, "(Enter) Confirm   (Esc) Cancel");
+
+    let digit = match ctx.key {
+        Some(VirtualKeyCode::Key0) | Some(VirtualKeyCode::Numpad0) => Some('0'),
+        Some(VirtualKeyCode::Key1) | Some(VirtualKeyCode::Numpad1) => Some('1'),
+        Some(VirtualKeyCode::Key2) | Some(VirtualKeyCode::Numpad2) => Some('2'),
+        Some(VirtualKeyCode::Key3) | Some(VirtualKeyCode::Numpad3) => Some('3'),
+        Some(VirtualKeyCode::Key4) | Some(VirtualKeyCode::Numpad4) => Some('4'),
+        Some(VirtualKeyCode::Key5) | Some(VirtualKeyCode::Numpad5) => Some('5'),
+        Some(VirtualKeyCode::Key6) | Some(VirtualKeyCode::Numpad6) => Some('6'),
+        Some(VirtualKeyCode::Key7) | Some(VirtualKeyCode::Numpad7) => Some('7'),
+        Some(VirtualKeyCode::Key8) | Some(VirtualKeyCode::Numpad8) => Some('8'),
+        Some(VirtualKeyCode::Key9) | Some(VirtualKeyCode::Numpad9) => Some('9'),
+        _ => None
+    };
+
+    if let Some(digit) = digit {
+        let mut text = input.text.clone();
+        text.push(digit);
+        if text.parse::<u64>().is_ok_and(|seed| seed < MAX_SEED) {
+            input.text = text;
+        }
+        return EnterSeedResult::NoResponse
+    }
+
+    match ctx.key {
+        Some(VirtualKeyCode::Back) => {
+            input.text.pop();
+            EnterSeedResult::NoResponse
+        }
+        Some(VirtualKeyCode::Escape) => EnterSeedResult::Cancel,
+        Some(VirtualKeyCode::Return) => {
+            match input.text.parse::<u64>() {
+                Ok(seed) => EnterSeedResult::Selected{seed},
+                Err(_) => EnterSeedResult::Cancel
+            }
+        }
+        _ => EnterSeedResult::NoResponse
+    }
+}
+
+/// Shows an error in place of the main menu, returns true once a key is pressed
+fn error_screen(ctx: &mut Rltk, title: &str, message: &str) -> bool {
+    ctx.print_color_centered(15, Palette::COLOR_2, Palette::MAIN_BG, "Roguelike");
+    ctx.print_color_centered(23, Palette::COLOR_1, Palette::MAIN_BG, title);
+
+    // Wrap the error message on multiple lines
+    let mut lines: Vec<String> = vec![String::new()];
+    for word in message.split_whitespace() {
+        let line = lines.last_mut().unwrap();
+        if !line.is_empty() && line.len() + word.len() + 1 > 76 {
+            lines.push(word.to_string());
+        } else {
+            if !line.is_empty() { line.push(' '); }
+            line.push_str(word);
+        }
+    }
+    for (i, line) in lines.iter().enumerate() {
+        ctx.print_color_centered(26 + i as i32, Palette::MAIN_FG, Palette::MAIN_BG, line);
+    }
+
+    ctx.print_color_centered(35, Palette::MAIN_FG, Palette::MAIN_BG, "Press any key to return to the main menu.");
+    ctx.key.is_some()
+}
+
+pub fn save_slots(res: &mut Resources, ctx: &mut Rltk) -> SaveSlotResult {
+    let runstate = *res.get::<RunState>().unwrap();
+    let slot_list = res.get::<SaveSlotList>().unwrap();
+
+    let (mode, selected, confirm) = match runstate {
+        RunState::SaveSlots{mode, selected, confirm} => (mode, selected, confirm),
+        _ => return SaveSlotResult::Cancel
+    };
+
+    let title = match mode {
+        SaveSlotMode::NewGame => "Choose a slot for the new adventure",
+        SaveSlotMode::LoadGame => "Choose a game to load"
+    };
+    ctx.print_color_centered(8, Palette::COLOR_2, Palette::MAIN_BG, title);
+
+    for (i, slot) in slot_list.slots.iter().enumerate() {
+        let y = 12 + 5 * i as i32;
+        let fg = if i == selected { Palette::COLOR_1 } else { Palette::MAIN_FG };
+        ctx.draw_box(15, y, 49, 3, fg, Palette::MAIN_BG);
+        ctx.print_color(17, y, fg, Palette::MAIN_BG, format!("Slot {}", i + 1));
+        match slot {
+            SlotInfo::Empty => {
+                ctx.print_color(17, y + 1, Palette::COLOR_2, Palette::MAIN_BG, "Empty");
+            }
+            SlotInfo::Saved(summary) => {
+                ctx.print_color(17, y + 1, fg, Palette::MAIN_BG, format!("{}  Depth: {}  {} / {} HP", summary.name, summary.depth, summary.hp, summary.max_hp));
+                ctx.print_color(17, y + 2, Palette::COLOR_2, Palette::MAIN_BG, format!("Saved {}", format_save_time(summary.saved_at)));
+            }
+            SlotInfo::Unreadable(error) => {
+                ctx.print_color(17, y + 1, Palette::COLOR_3, Palette::MAIN_BG, "Unreadable save");
+                ctx.print_color(17, y + 2, Palette::COLOR_2, Palette::MAIN_BG, error.chars().take(45).collect::<String>());
+            }
+        }
+    }
+
+    ctx.print_color_centered(40, Palette::MAIN_FG, Palette::MAIN_BG, "(Enter) Select   (d) Delete   (Esc) Back");
+
+    let is_empty = matches!(slot_list.slots[selected], SlotInfo::Empty);
+
+    if let Some(confirmation) = confirm {
+        let question = match confirmation {
+            SlotConfirmation::Overwrite => format!("Overwrite slot {}? (y/n)", selected + 1),
+            SlotConfirmation::Delete => format!("Delete slot {}? (y/n)", selected + 1)
+        };
+        ctx.draw_box(25, 23, 29, 2, Palette::COLOR_1, Palette::MAIN_BG);
+        ctx.print_color_centered(24, Palette::COLOR_1, Palette::MAIN_BG, question);
+
+        return match ctx.key {
+            Some(VirtualKeyCode::Y) => match confirmation {
+                SlotConfirmation::Overwrite => SaveSlotResult::Selected{slot: selected},
+                SlotConfirmation::Delete => SaveSlotResult::Delete{slot: selected}
+            },
+            Some(VirtualKeyCode::N) | Some(VirtualKeyCode::Escape) => SaveSlotResult::NoSelection{selected, confirm: None},
+            _ => SaveSlotResult::NoSelection{selected, confirm}
+        }
+    }
+
+    match ctx.key {
+        Some(VirtualKeyCode::Escape) => SaveSlotResult::Cancel,
+        Some(VirtualKeyCode::Up) => SaveSlotResult::NoSelection{selected: (selected + NB_SAVE_SLOTS - 1) % NB_SAVE_SLOTS, confirm},
+        Some(VirtualKeyCode::Down) => SaveSlotResult::NoSelection{selected: (selected + 1) % NB_SAVE_SLOTS, confirm},
+        Some(VirtualKeyCode::D) if !is_empty => SaveSlotResult::NoSelection{selected, confirm: Some(SlotConfirmation::Delete)},
+        Some(VirtualKeyCode::Return) => {
+            match mode {
+                SaveSlotMode::NewGame if !is_empty => SaveSlotResult::NoSelection{selected, confirm: Some(SlotConfirmation::Overwrite)},
+                SaveSlotMode::LoadGame if is_empty => SaveSlotResult::NoSelection{selected, confirm},
+                _ => SaveSlotResult::Selected{slot: selected}
+            }
+        }
+        _ => SaveSlotResult::NoSelection{selected, confirm}
+    }
+}
+
+// Formats seconds since the unix epoch as a UTC date and time
+fn format_save_time(secs: u64) -> String {
+    if secs == 0 { return "at an unknown time".to_string() }
+
+    // Days to civil date conversion from Howard Hinnant's date algorithms
+    let days = (secs / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let doe = days.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    let secs_of_day = secs % 86400;
+    format!("{:04}-{:02}-{:02} {:02}:{:02} UTC", year, month, day, secs_of_day / 3600, (secs_of_day % 3600) / 60)
+}
+
+/// Shows why the replay stopped, returns true once a key is pressed
+pub fn replay_report(res: &Resources, ctx: &mut Rltk) -> bool {
+    let replay = res.get::<Replay>().unwrap();
+    let report = replay.report.clone().unwrap_or_default();
+
+    ctx.draw_box(2, 20, 75, 8, Palette::COLOR_1, Palette::MAIN_BG);
+    ctx.print_color(4, 20, Palette::COLOR_1, Palette::MAIN_BG, "Replay report");
+    let width = 71;
+    let chars: Vec<char> = report.chars().collect();
+    for (i, line) in chars.chunks(width).take(5).enumerate() {
+        ctx.print_color(4, 22 + i as i32, Palette::MAIN_FG, Palette::MAIN_BG, line.iter().collect::<String>());
+    }
+    ctx.print_color(4, 27, Palette::MAIN_FG, Palette::MAIN_BG, "Press any key to return to the main menu.");
+
+    ctx.key.is_some()
+}
+
+fn draw_high_scores(table: &HighScoreTable, ctx: &mut Rltk, y: i32) {
+    ctx.print_color(5, y, Palette::COLOR_2, Palette::MAIN_BG, format!("{:<4}{:<14}{:>6}{:>7}{:>7}{:>7}  {}", "", "Name", "Score", "Depth", "Kills", "Turns", "Killed by"));
+    if table.scores.is_empty() {
+        ctx.print_color_centered(y + 2, Palette::MAIN_FG, Palette::MAIN_BG, "No one has died yet");
+    }
+    for (i, high_score) in table.scores.iter().enumerate() {
+        let fg = if table.highlighted == Some(i) { Palette::COLOR_1 } else { Palette::MAIN_FG };
+        let name: String = high_score.name.chars().take(13).collect();
+        let killed_by: String = high_score.killed_by.chars().take(20).collect();
+        ctx.print_color(5, y + 2 + i as i32, fg, Palette::MAIN_BG,
+            format!("{:<4}{:<14}{:>6}{:>7}{:>7}{:>7}  {}", format!("{}.", i + 1), name, high_score.score, high_score.depth, high_score.monsters_killed, high_score.turns, killed_by));
+    }
+}
+
+/// Shows the high score table from the main menu, returns true once a key is pressed
+pub fn high_scores(res: &Resources, ctx: &mut Rltk) -> bool {
+    ctx.print_color_centered(8, Palette::COLOR_2, Palette::MAIN_BG, "High scores");
+    draw_high_scores(&res.get::<HighScoreTable>().unwrap(), ctx, 14);
+    ctx.print_color_centered(40, Palette::MAIN_FG, Palette::MAIN_BG, "Press any key to return to the main menu.");
+
+    ctx.key.is_some()
+}
+
+pub fn game_over(res: &Resources, ctx: &mut Rltk) -> GameOverResult {
+    ctx.print_color_centered(8, Palette::MAIN_FG, Palette::MAIN_BG, "You are dead.");
+    if let Ok(table) = res.get::<HighScoreTable>() {
+        if table.highlighted.is_none() {
+            ctx.print_color_centered(10, Palette::MAIN_FG, Palette::MAIN_BG, "Your run did not make it to the high scores");
+        }
+        draw_high_scores(&table, ctx, 14);
+    }
+    draw_run_report(res, ctx, 30);
+    ctx.print_color_centered(40, Palette::MAIN_FG, Palette::MAIN_BG, "Press any key to return to the main menu.");
     match ctx.key {
-        None => { return GameOverResult::NoSelection }
-        Some(_key) => { return GameOverResult::QuitToMenu }
+        None => GameOverResult::NoSelection,
+        Some(_key) => GameOverResult::QuitToMenu
     }
 }
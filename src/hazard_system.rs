@@ -0,0 +1,32 @@
+use hecs::*;
+use resources::Resources;
+use crate::RunState;
+use crate::components::{CombatStats, Player, Position, TakeDamage};
+use crate::gamelog::GameLog;
+use crate::map::Map;
+
+const HAZARD_DAMAGE: i32 = 5;
+
+
+/// Hurts whoever stands on hazardous terrain such as lava, once per turn
+pub fn hazards(world: &mut World, res: &mut Resources) {
+    let runstate: &RunState = &res.get::<RunState>().unwrap();
+    if *runstate != RunState::PlayerTurn { return; }
+
+    let map = res.get::<Map>().unwrap();
+    let mut log = res.get_mut::<GameLog>().unwrap();
+
+    let mut hurt: Vec<(Entity, &str)> = Vec::new();
+    for (id, (pos, _stats, player)) in world.query::<(&Position, &CombatStats, Option<&Player>)>().iter() {
+        if let Some(hazard) = map.tiles[map.xy_idx(pos.x, pos.y)].hazard_name() {
+            hurt.push((id, hazard));
+            if player.is_some() {
+                log.messages.push(format!("The {} hurts you for {} hp", hazard, HAZARD_DAMAGE));
+            }
+        }
+    }
+
+    for (id, hazard) in hurt {
+        TakeDamage::add_damage(world, id, HAZARD_DAMAGE, hazard);
+    }
+}
@@ -0,0 +1,300 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use hecs::*;
+use resources::Resources;
+use rltk::{DijkstraMap, Point, RandomNumberGenerator};
+
+use crate::{RunState, RunStats, State, damage_system, particle_system};
+use crate::components::CombatStats;
+use crate::map::{Map, TileType};
+use crate::replay::Command;
+use crate::rng::RunSeed;
+
+/// Time that passes on each step, so particles fade out as they would at 60 frames per second
+const STEP_TIME_MS: f32 = 1000.0 / 60.0;
+
+const DIRECTIONS: [(i32, i32); 8] = [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)];
+
+/// Runs a game without a window, the player commands are taken from a queue instead of the keyboard
+pub struct HeadlessGame {
+    state: State,
+    commands: VecDeque<Command>
+}
+
+impl HeadlessGame {
+    /// Starts a new game on the first level of the given seed
+    pub fn new(seed: RunSeed) -> HeadlessGame {
+        let mut state = State::new();
+        state.resources.insert(seed);
+        state.new_game();
+        state.resources.insert(RunState::PreRun);
+
+        HeadlessGame{state, commands: VecDeque::new()}
+    }
+
+    pub fn world(&self) -> &World {
+        &self.state.world
+    }
+
+    pub fn resources(&self) -> &Resources {
+        &self.state.resources
+    }
+
+    pub fn runstate(&self) -> RunState {
+        *self.state.resources.get::<RunState>().unwrap()
+    }
+
+    pub fn turns(&self) -> u32 {
+        self.state.resources.get::<RunStats>().unwrap().turns
+    }
+
+    pub fn push_command(&mut self, command: Command) {
+        self.commands.push_back(command);
+    }
+
+    /// Advances the game by one state like a frame of `tick` does, returns false when it cannot
+    /// go further because the player is dead or it waits for a command and the queue is empty
+    pub fn step(&mut self) -> bool {
+        let runstate = self.runstate();
+        let new_runstate = match runstate {
+            RunState::GameOver => return false,
+            RunState::AwaitingInput | RunState::ShowInventory | RunState::ShowItemActions{..} | RunState::ShowTargeting{..} => {
+                match self.commands.pop_front() {
+                    None => return false,
+                    Some(command) => self.state.advance(runstate, Some(command))
+                }
+            }
+            _ => self.state.advance(runstate, None)
+        };
+
+        particle_system::update_particles(&mut self.state.world, &mut self.state.resources, STEP_TIME_MS);
+        self.state.resources.insert::<RunState>(new_runstate);
+        damage_system::delete_the_dead(&mut self.state.world, &mut self.state.resources);
+        true
+    }
+
+    /// The command that brings the player one step closer to the stairs down, or takes them when
+    /// the player stands on them. None when the level has no stairs down or the way is blocked
+    pub fn towards_stairs_down(&self) -> Option<Command> {
+        let map = self.state.resources.get::<Map>().unwrap();
+        let stairs_idx = map.tiles.iter().position(|tile| *tile == TileType::StairsDown)?;
+        let player_pos = *self.state.resources.get::<Point>().unwrap();
+        let player_idx = map.xy_idx(player_pos.x, player_pos.y);
+        if player_idx == stairs_idx {
+            return Some(Command::Descend);
+        }
+
+        let dijkstra_map = DijkstraMap::new(map.width as usize, map.height as usize, &[stairs_idx], &*map, map.tiles.len() as f32);
+        let next_idx = DijkstraMap::find_lowest_exit(&dijkstra_map, player_idx, &*map)?;
+        let (x, y) = map.idx_xy(next_idx);
+        Some(Command::Move{dx: x - player_pos.x, dy: y - player_pos.y})
+    }
+
+    /// Plays every queued command, returns the state the game stopped in
+    pub fn run(&mut self) -> RunState {
+        while self.step() {}
+        self.runstate()
+    }
+}
+
+/// Where a simulated run ended up
+#[derive(Copy, Clone, PartialEq)]
+pub struct SimulationResult {
+    pub seed: RunSeed,
+    pub turns: u32,
+    pub depth: i32,
+    pub deepest_depth: i32,
+    pub hp: i32,
+    pub max_hp: i32,
+    pub nb_entities: usize,
+    pub died: bool
+}
+
+impl fmt::Display for SimulationResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let outcome = if self.died { "died" } else { "survived" };
+        write!(f, "Seed {}: the player {} after {} turns at depth {} (deepest {}) with {}/{} hp, {} entities",
+            self.seed.0, outcome, self.turns, self.depth, self.deepest_depth, self.hp, self.max_hp, self.nb_entities)
+    }
+}
+
+/// Plays a run of `nb_turns` random commands and tells where the player ended up, used to
+/// check that long runs do not crash: roguelike --simulate <turns> [--seed <seed>]
+pub fn simulate(seed: RunSeed, nb_turns: u32) -> SimulationResult {
+    let mut game = HeadlessGame::new(seed);
+    let mut rng = RandomNumberGenerator::seeded(seed.0);
+
+    while game.turns() < nb_turns && game.run() == RunState::AwaitingInput {
+        // Half of the time the player heads down the dungeon, otherwise it wanders around
+        let command = match rng.roll_dice(1, 20) {
+            1 => Some(Command::Wait),
+            2 => Some(Command::PickUp),
+            3..=12 => game.towards_stairs_down(),
+            _ => None
+        };
+        let command = command.unwrap_or_else(|| {
+            let (dx, dy) = DIRECTIONS[rng.range(0, DIRECTIONS.len() as i32) as usize];
+            Command::Move{dx, dy}
+        });
+        game.push_command(command);
+    }
+
+    let player_id = *game.resources().get::<Entity>().unwrap();
+    let stats = *game.world().get::<CombatStats>(player_id).unwrap();
+    let depth = game.resources().get::<Map>().unwrap().depth;
+    let deepest_depth = game.resources().get::<RunStats>().unwrap().deepest_depth;
+    SimulationResult {
+        seed,
+        turns: game.turns(),
+        depth,
+        deepest_depth,
+        hp: stats.hp,
+        max_hp: stats.max_hp,
+        nb_entities: game.world().iter().count(),
+        died: game.runstate() == RunState::GameOver
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{map_indexing_system, spawner};
+    use crate::dungeon::Dungeon;
+    use crate::map_builders::DungeonLayout;
+    use crate::components::{Name, Player, Position};
+
+    fn player_pos(game: &HeadlessGame) -> Position {
+        let player_id = *game.resources().get::<Entity>().unwrap();
+        *game.world().get::<Position>(player_id).unwrap()
+    }
+
+    /// The names and positions of everything on the current level but the player
+    fn level_entities(game: &HeadlessGame) -> Vec<(String, i32, i32)> {
+        let mut entities: Vec<(String, i32, i32)> = game.world().query::<(&Name, &Position)>().without::<Player>().iter()
+            .map(|(_id, (name, pos))| (name.name.clone(), pos.x, pos.y))
+            .collect();
+        entities.sort();
+        entities
+    }
+
+    #[test]
+    fn bumping_into_a_goblin_hurts_it() {
+        let mut game = HeadlessGame::new(RunSeed(7));
+        game.run();
+
+        // Put a goblin on a free tile next to the player
+        let pos = player_pos(&game);
+        let (dx, dy) = {
+            let map = game.resources().get::<Map>().unwrap();
+            *DIRECTIONS.iter().find(|(dx, dy)| {
+                let idx = map.xy_idx(pos.x + dx, pos.y + dy);
+                !map.blocked[idx] && map.tile_content[idx].is_empty()
+            }).expect("The player is surrounded")
+        };
+        spawner::spawn_entity(&mut game.state.world, (pos.x + dx, pos.y + dy), "Goblin");
+        map_indexing_system::map_indexing(&mut game.state.world, &mut game.state.resources);
+        let goblin = game.world().query::<&Name>().iter()
+            .find(|(_id, name)| name.name == "Goblin")
+            .map(|(id, _name)| id)
+            .unwrap();
+
+        game.push_command(Command::Move{dx, dy});
+        game.run();
+
+        let stats = game.world().get::<CombatStats>(goblin).unwrap();
+        assert!(stats.hp < stats.max_hp);
+    }
+
+    #[test]
+    fn going_back_up_restores_the_previous_level() {
+        let mut game = HeadlessGame::new(RunSeed(11));
+        game.run();
+
+        let stairs = {
+            let map = game.resources().get::<Map>().unwrap();
+            let idx = map.tiles.iter().position(|tile| *tile == TileType::StairsDown).expect("The town has no stairs down");
+            map.idx_xy(idx)
+        };
+        game.state.move_player(Position{x: stairs.0, y: stairs.1});
+        let town_entities = level_entities(&game);
+
+        game.push_command(Command::Descend);
+        game.run();
+        assert_eq!(game.resources().get::<Map>().unwrap().depth, 1);
+        assert_ne!(level_entities(&game), town_entities);
+
+        game.push_command(Command::Ascend);
+        game.run();
+        assert_eq!(game.resources().get::<Map>().unwrap().depth, 0);
+        assert_eq!(level_entities(&game), town_entities);
+        let pos = player_pos(&game);
+        assert_eq!((pos.x, pos.y), stairs);
+    }
+
+    #[test]
+    fn going_up_to_a_level_without_stairs_down_lands_on_a_walkable_tile() {
+        let mut game = HeadlessGame::new(RunSeed(11));
+        game.run();
+        let stairs = {
+            let map = game.resources().get::<Map>().unwrap();
+            map.idx_xy(map.tiles.iter().position(|tile| *tile == TileType::StairsDown).unwrap())
+        };
+        game.state.move_player(Position{x: stairs.0, y: stairs.1});
+        game.push_command(Command::Descend);
+        game.run();
+
+        // The stairs of the stored town disappear while the player is below
+        {
+            let mut dungeon = game.state.resources.get_mut::<Dungeon>().unwrap();
+            let mut town = dungeon.get_map(0).unwrap();
+            town.set_tile(stairs.0, stairs.1, TileType::Grass);
+            dungeon.store_map(&town);
+        }
+
+        game.push_command(Command::Ascend);
+        game.run();
+        let map = game.resources().get::<Map>().unwrap();
+        let pos = player_pos(&game);
+        assert_eq!(map.depth, 0);
+        assert_ne!(map.xy_idx(pos.x, pos.y), 0);
+        assert!(map.tiles[map.xy_idx(pos.x, pos.y)].is_walkable());
+    }
+
+    #[test]
+    fn the_boss_guards_the_stairs_of_the_boss_floor() {
+        let mut game = HeadlessGame::new(RunSeed(13));
+        let boss_depth = game.resources().get::<DungeonLayout>().unwrap().levels.iter()
+            .find(|level| level.boss.is_some())
+            .map(|level| level.min_depth)
+            .unwrap();
+        game.state.change_level(boss_depth);
+
+        let map = game.resources().get::<Map>().unwrap();
+        let stairs = map.idx_xy(map.tiles.iter().position(|tile| *tile == TileType::StairsDown).unwrap());
+        let bosses: Vec<(i32, i32)> = game.world().query::<(&Name, &Position)>().iter()
+            .filter(|(_id, (name, _pos))| name.name == "Orc Warlord")
+            .map(|(_id, (_name, pos))| (pos.x, pos.y))
+            .collect();
+        assert_eq!(bosses, vec![stairs]);
+    }
+
+    #[test]
+    fn simulated_runs_go_down_the_dungeon() {
+        let result = simulate(RunSeed(5), 1000);
+        assert!(result.deepest_depth >= 1, "{}", result);
+    }
+
+    #[test]
+    fn thousands_of_turns_do_not_panic() {
+        // Runs end when the player dies, so several seeds are played to reach the total
+        let mut total_turns = 0;
+        let mut seed = 0;
+        while total_turns < 5000 {
+            seed += 1;
+            let result = simulate(RunSeed(seed), 2000);
+            assert!(result.died || result.turns >= 2000, "{}", result);
+            total_turns += result.turns;
+        }
+    }
+}
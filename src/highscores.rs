@@ -0,0 +1,73 @@
+use std::fs;
+use std::path::Path;
+
+use hecs::*;
+use resources::Resources;
+use serde::{Serialize, Deserialize};
+
+use crate::RunStats;
+use crate::components::{Name, TakeDamage};
+use crate::rng::RunSeed;
+use crate::saveload_system::write_atomically;
+
+const HIGH_SCORE_FILE: &str = "./highscores.json";
+pub const NB_HIGH_SCORES: usize = 10;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HighScore {
+    pub name: String,
+    pub score: u32,
+    pub depth: i32,
+    pub monsters_killed: u32,
+    pub turns: u32,
+    pub killed_by: String,
+    pub seed: u64
+}
+
+/// The best runs ranked by score, `highlighted` is the rank of the run that just ended
+pub struct HighScoreTable {
+    pub scores: Vec<HighScore>,
+    pub highlighted: Option<usize>
+}
+
+/// Going deeper is worth the most, then killing monsters, and surviving longer breaks ties
+pub fn score(stats: &RunStats) -> u32 {
+    stats.deepest_depth.max(0) as u32 * 100 + stats.monsters_killed * 10 + stats.turns / 10
+}
+
+/// Reads the high scores, a missing or unreadable file is an empty table
+pub fn read_high_scores() -> HighScoreTable {
+    let scores = fs::read_to_string(HIGH_SCORE_FILE).ok()
+        .and_then(|json| serde_json::from_str::<Vec<HighScore>>(&json).ok())
+        .unwrap_or_default();
+    HighScoreTable{scores, highlighted: None}
+}
+
+/// Adds the run the player just died in to the high scores, the returned table highlights
+/// it if it made the cut
+pub fn record_run(world: &World, res: &Resources) -> Result<HighScoreTable, String> {
+    let player_id = *res.get::<Entity>().unwrap();
+    let stats = *res.get::<RunStats>().unwrap();
+    let entry = HighScore {
+        name: world.get::<Name>(player_id).map(|name| name.name.clone()).unwrap_or_else(|_| "Player".to_string()),
+        score: score(&stats),
+        depth: stats.deepest_depth,
+        monsters_killed: stats.monsters_killed,
+        turns: stats.turns,
+        killed_by: world.get::<TakeDamage>(player_id).map(|damage| damage.last_source.clone()).unwrap_or_else(|_| "something unknown".to_string()),
+        seed: res.get::<RunSeed>().unwrap().0
+    };
+
+    // Ties are ranked by age, the older run stays ahead
+    let mut table = read_high_scores();
+    let rank = table.scores.iter().position(|high_score| high_score.score < entry.score).unwrap_or(table.scores.len());
+    table.scores.insert(rank, entry);
+    table.scores.truncate(NB_HIGH_SCORES);
+    if rank < NB_HIGH_SCORES {
+        table.highlighted = Some(rank);
+    }
+
+    let json = serde_json::to_string(&table.scores).map_err(|e| e.to_string())?;
+    write_atomically(Path::new(HIGH_SCORE_FILE), &json).map_err(|e| e.to_string())?;
+    Ok(table)
+}
@@ -11,7 +11,7 @@ pub fn item_use(world: &mut World, res: &mut Resources) {
     let mut p_builder = res.get_mut::<ParticleBuilder>().unwrap();
     let mut to_remove: Vec<Entity> = Vec::new();
     let mut to_remove_wants_use: Vec<Entity> = Vec::new();
-    let mut to_add_take_damage: Vec<(Entity, DealsDamage)> = Vec::new();
+    let mut to_add_take_damage: Vec<(Entity, DealsDamage, String)> = Vec::new();
     let mut to_heal: Vec<(Entity, ProvidesHealing)> = Vec::new();
     let mut to_add_confusion: Vec<(Entity, Confusion)> = Vec::new();
     let mut to_unequip: Vec<(Entity, Name, Entity)> = Vec::new();
@@ -91,8 +91,9 @@ pub fn item_use(world: &mut World, res: &mut Resources) {
             Err(_e) => {}
             Ok(dd) => {
                 used_item = false;
+                let item_name = world.get::<Name>(use_item.item).unwrap().name.clone();
                 for target in targets.iter() {
-                    to_add_take_damage.push((*target, *dd));
+                    to_add_take_damage.push((*target, *dd, item_name.clone()));
                     if id == *player_id {
                         let monster_name = world.get::<Name>(*target).unwrap();
                         let item_name = world.get::<Name>(use_item.item).unwrap();
@@ -169,8 +170,8 @@ pub fn item_use(world: &mut World, res: &mut Resources) {
         world.remove_one::<WantsToUseItem>(id).unwrap();
     }
 
-    for (id, deals_damage) in to_add_take_damage {
-        TakeDamage::add_damage(world, id, deals_damage.damage);
+    for (id, deals_damage, source) in to_add_take_damage {
+        TakeDamage::add_damage(world, id, deals_damage.damage, &source);
     }
 
     for (id, heals) in to_heal {
@@ -1,16 +1,20 @@
-use map_builders::MapGenData;
-use rltk::{Rltk, GameState, RltkBuilder, Point};
+use map_builders::{DungeonLayout, LayoutError, MapGenData};
+use rltk::{Rltk, GameState, RltkBuilder, Point, VirtualKeyCode};
 use hecs::*;
 use resources::Resources;
+use serde::{Serialize, Deserialize};
 
 mod player;
 mod map;
+mod camera;
 mod components;
 mod visibility_system;
 mod monster_ai_system;
+mod npc_ai_system;
 mod map_indexing_system;
 mod melee_combat_system;
 mod damage_system;
+mod hazard_system;
 mod inventory_system;
 mod drop_item_system;
 mod item_use_system;
@@ -21,11 +25,23 @@ mod spawner;
 mod weighted_table;
 mod unequip_item_system;
 mod particle_system;
+mod dungeon;
+mod rng;
+mod replay;
+mod headless;
+mod morgue;
+mod highscores;
+mod saveload_system;
+mod save_migrations;
 
 pub mod map_builders;
 
-use components::{Position, Renderable, WantsToUseItem, WantsToDropItem, Ranged, InBackpack, Player, Viewshed, Equipped, WantsToUnequipItem};
-use map::Map;
+use components::{Position, Renderable, WantsToUseItem, WantsToDropItem, Ranged, Viewshed, WantsToUnequipItem};
+use map::{Map, TileType};
+use camera::Camera;
+use dungeon::Dungeon;
+use rng::{RunSeed, RandomStream};
+use replay::{Command, Checkpoint, Recording, Replay};
 use gamelog::GameLog;
 
 pub struct Palette;
@@ -38,6 +54,10 @@ impl Palette {
     const COLOR_2: rltk::RGB = rltk::RGB{r: 0., g: 0.2, b: 0.};
     const COLOR_3: rltk::RGB = rltk::RGB{r: 0.7, g: 0.2, b: 0.2};
     const COLOR_4: rltk::RGB = rltk::RGB{r: 0.7, g:0.7, b:0.};
+    const GRASS: rltk::RGB = rltk::RGB{r: 0.1, g: 0.5, b: 0.1};
+    const WATER: rltk::RGB = rltk::RGB{r: 0.2, g: 0.4, b: 1.};
+    const DEEP_WATER_BG: rltk::RGB = rltk::RGB{r: 0., g: 0., b: 0.3};
+    const LAVA: rltk::RGB = rltk::RGB{r: 1., g: 0.4, b: 0.};
 }
 
 const SHOW_MAPGEN_ANIMATION: bool = true;
@@ -53,13 +73,18 @@ pub enum RunState {
     ShowItemActions {item: Entity},
     ShowTargeting {range: i32, item: Entity},
     MainMenu {menu_selection: gui::MainMenuSelection},
+    EnterSeed,
+    SaveSlots {mode: gui::SaveSlotMode, selected: usize, confirm: Option<gui::SlotConfirmation>},
     SaveGame,
     NextLevel,
+    PreviousLevel,
     GameOver,
-    MapGenAnimation
+    HighScores,
+    MapGenAnimation,
+    ReplayEnded
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Ord, PartialOrd)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Ord, PartialOrd, Serialize, Deserialize)]
 pub enum RenderOrder {
     Items,
     NPC,
@@ -67,6 +92,14 @@ pub enum RenderOrder {
     Particle
 }
 
+/// What the player achieved since the start of the run
+#[derive(Copy, Clone, Default, Serialize, Deserialize)]
+pub struct RunStats {
+    pub turns: u32,
+    pub monsters_killed: u32,
+    pub deepest_depth: i32
+}
+
 pub struct State {
     world: World,
     resources: Resources,
@@ -74,135 +107,401 @@ pub struct State {
 }
 
 impl State {
+    fn new() -> State {
+        let mut gs = State {
+            world: World::new(),
+            resources: Resources::default(),
+            mapgen_data: MapGenData{history: Vec::new(), timer: 0.0, index: 0}
+        };
+
+        gs.resources.insert(Map::new(1, map::MAPWIDTH, map::MAPHEIGHT));
+        gs.resources.insert(Point::new(0, 0));
+        gs.resources.insert(rltk::RandomNumberGenerator::new());
+        gs.resources.insert(RunSeed::random());
+
+        gs.resources.insert(saveload_system::SaveSlot(0));
+        let save_files = saveload_system::SaveFiles::scan();
+        let menu_selection = if save_files.autosave { gui::MainMenuSelection::Continue } else { gui::MainMenuSelection::NewGame };
+        gs.resources.insert(save_files);
+        gs.resources.insert(RunState::MainMenu{menu_selection});
+        gs.resources.insert(gamelog::GameLog{messages: vec!["Welcome to the roguelike!".to_string()]});
+        gs.resources.insert(particle_system::ParticleBuilder::new());
+        gs.resources.insert(Dungeon::default());
+        gs.resources.insert(RunStats::default());
+        match DungeonLayout::load() {
+            Ok(layout) => { gs.resources.insert(layout); }
+            Err(e) => {
+                gs.resources.insert(DungeonLayout::builtin());
+                gs.resources.insert(LayoutError(e));
+            }
+        }
+
+        gs
+    }
+
     fn run_systems(&mut self) {
-        visibility_system::visibility(&mut self.world, &mut self.resources);
         monster_ai_system::monster_ai(&mut self.world, &mut self.resources);
+        npc_ai_system::npc_ai(&mut self.world, &mut self.resources);
+        // Indexed before the field of view is computed, closed doors block the view
         map_indexing_system::map_indexing(&mut self.world, &mut self.resources);
+        visibility_system::visibility(&mut self.world, &mut self.resources);
         melee_combat_system::melee_combat(&mut self.world, &mut self.resources);
         inventory_system::inventory(&mut self.world, &mut self.resources);
         drop_item_system::drop_item(&mut self.world, &mut self.resources);
         unequip_item_system::unequip_item(&mut self.world, &mut self.resources);
         item_use_system::item_use(&mut self.world, &mut self.resources);
+        hazard_system::hazards(&mut self.world, &mut self.resources);
         damage_system::damage(&mut self.world);
         particle_system::spawn_particles(&mut self.world, &mut self.resources);
     }
 
-    fn entities_to_delete_on_level_change(&mut self) -> Vec<Entity> {
-        let mut ids_to_delete: Vec<Entity> = Vec::new();
-        let all_entities: Vec<Entity> = self.world.iter().map(|(id, _)| id).collect();
-
-        let player_id = self.resources.get::<Entity>().unwrap();
-
-        for id in all_entities {
-            let mut to_delete = true;
-            if let Ok(_p) =  self.world.get::<Player>(id) { to_delete = false; }
-            
-            if let Ok(backpack) = self.world.get::<InBackpack>(id) {
-                if backpack.owner == *player_id { to_delete = false; }
-            }
-
-            if let Ok(equipped) = self.world.get::<Equipped>(id) {
-                if equipped.owner == *player_id { to_delete = false; }
-            }
-
-            if to_delete { ids_to_delete.push(id); }
-        }
-
-        ids_to_delete
-    }
-
     fn generate_map(&mut self, new_depth: i32) {
         self.mapgen_data.index = 0;
         self.mapgen_data.timer = 0.0;
         self.mapgen_data.history.clear();
 
-        // Generate map
-        let mut map_builder = map_builders::random_builder(new_depth);
-        map_builder.build_map();
+        // Generate map, each depth has its own random stream
+        let seed = *self.resources.get::<RunSeed>().unwrap();
+        let mut rng = rng::stream_rng(seed, RandomStream::Map, new_depth);
+        let layout = self.resources.get::<DungeonLayout>().unwrap().clone();
+        let mut builder = map_builders::build_level(&layout, new_depth, &mut rng);
 
-        self.mapgen_data.history = map_builder.get_map_history();
+        self.mapgen_data.history = builder.build_data.history.clone();
 
-        let start_pos;
+        let start_pos = builder.build_data.starting_position.expect("The level has no starting position");
         {
             let mut map = self.resources.get_mut::<Map>().unwrap();
-            *map = map_builder.get_map();
-            start_pos = map_builder.get_starting_position();
+            *map = builder.build_data.map.clone();
+
+            // Place a way back up where the player arrives
+            if new_depth > 0 {
+                map.set_tile(start_pos.x, start_pos.y, TileType::StairsUp);
+            }
+
+            // The boss of the floor guards the stairs down, in place of whatever was spawned there
+            if let Some(boss) = layout.level(new_depth).boss.clone() {
+                let boss_pos = match map.tiles.iter().position(|tile| *tile == TileType::StairsDown) {
+                    Some(stairs_idx) => map.idx_xy(stairs_idx),
+                    None => map.closest_walkable_tile(map.width / 2, map.height / 2).expect("The level has no walkable tile")
+                };
+                builder.build_data.spawn_list.retain(|(pos, _name)| *pos != boss_pos);
+                builder.build_data.spawn_list.push((boss_pos, boss));
+            }
+
+            // Nothing lies below the last level
+            if layout.level(new_depth).last_level {
+                for tile in map.tiles.iter_mut().filter(|tile| **tile == TileType::StairsDown) {
+                    *tile = TileType::Floor;
+                }
+            }
         }
 
         // Spawn monsters and items
-        map_builder.spawn_entities(&mut self.world, &mut self.resources);
+        builder.spawn_entities(&mut self.world);
 
-        // Update player position
+        self.move_player(start_pos);
+    }
+
+    fn move_player(&mut self, new_pos: Position) {
         let mut player_position = self.resources.get_mut::<Point>().unwrap();
-        *player_position = Point::new(start_pos.x, start_pos.y);
+        *player_position = Point::new(new_pos.x, new_pos.y);
         let player_id = self.resources.get::<Entity>().unwrap();
         let mut player_pos_comp = self.world.get_mut::<Position>(*player_id).unwrap();
-        player_pos_comp.x = start_pos.x;
-        player_pos_comp.y = start_pos.y;
+        player_pos_comp.x = new_pos.x;
+        player_pos_comp.y = new_pos.y;
 
         // Mark viewshed as dirty
         let player_vs = self.world.get_mut::<Viewshed>(*player_id);
         if let Ok(mut vs) = player_vs { vs.dirty = true; }
     }
 
-    fn next_level(&mut self) {
-        // Delete all useless entities
-        let ids_to_delete = self.entities_to_delete_on_level_change();
-        for id in ids_to_delete {
-            self.world.despawn(id).unwrap();
+    fn change_level(&mut self, new_depth: i32) {
+        // Store the current level with its entities
+        {
+            let map = self.resources.get::<Map>().unwrap();
+            let mut dungeon = self.resources.get_mut::<Dungeon>().unwrap();
+            dungeon.store_map(&map);
+            dungeon::freeze_level_entities(&mut self.world, map.depth);
+        }
+
+        let visited_map = self.resources.get::<Dungeon>().unwrap().get_map(new_depth);
+        match visited_map {
+            None => self.generate_map(new_depth),
+            Some(map) => {
+                // Arrive on the stairs leading back to the level the player comes from
+                let current_depth = self.resources.get::<Map>().unwrap().depth;
+                let arrival_tile = if new_depth > current_depth { TileType::StairsUp } else { TileType::StairsDown };
+                let (x, y) = match map.tiles.iter().position(|tile| *tile == arrival_tile) {
+                    Some(arrival_idx) => map.idx_xy(arrival_idx),
+                    None => {
+                        // Stairs can be missing from painted levels, arrive as close as possible
+                        // to where the player stood instead
+                        let player_pos = *self.resources.get::<Point>().unwrap();
+                        map.closest_walkable_tile(player_pos.x, player_pos.y).expect("The level has no walkable tile")
+                    }
+                };
+
+                self.resources.insert(map);
+                dungeon::thaw_level_entities(&mut self.world, new_depth);
+                self.move_player(Position{x, y});
+            }
         }
+    }
 
-        // Generate new map
-        let current_depth;
+    fn next_level(&mut self) {
+        let current_depth = self.resources.get::<Map>().unwrap().depth;
+        self.change_level(current_depth + 1);
         {
-            let map = self.resources.get_mut::<Map>().unwrap();
-            current_depth = map.depth;
+            let mut stats = self.resources.get_mut::<RunStats>().unwrap();
+            stats.deepest_depth = i32::max(stats.deepest_depth, current_depth + 1);
         }
-        self.generate_map(current_depth + 1);
 
         // Notify player
         let mut log = self.resources.get_mut::<GameLog>().unwrap();
         log.messages.push("You descend in the staircase".to_string());
     }
 
-    fn game_over_cleanup(&mut self) {
+    fn previous_level(&mut self) {
+        let current_depth = self.resources.get::<Map>().unwrap().depth;
+        self.change_level(current_depth - 1);
+
+        // Notify player
+        let mut log = self.resources.get_mut::<GameLog>().unwrap();
+        log.messages.push("You climb up the staircase".to_string());
+    }
+
+    fn show_save_slots(&mut self, mode: gui::SaveSlotMode) -> RunState {
+        let slot_list = saveload_system::read_save_slots();
+
+        // Start on the first empty slot for a new game, and on the first saved one when loading
+        let selected = slot_list.slots.iter().position(|slot| {
+            matches!(slot, saveload_system::SlotInfo::Empty) == (mode == gui::SaveSlotMode::NewGame)
+        }).unwrap_or(0);
+
+        self.resources.insert(slot_list);
+        RunState::SaveSlots{mode, selected, confirm: None}
+    }
+
+    fn new_game(&mut self) {
         // Delete everything
         self.world.clear();
 
+        let seed = *self.resources.get::<RunSeed>().unwrap();
+        self.resources.insert(rng::stream_rng(seed, RandomStream::Combat, 0));
+        self.resources.insert(gamelog::GameLog{messages: vec!["Welcome to the roguelike!".to_string()]});
+        self.resources.insert(RunStats::default());
+        if !self.resources.contains::<Replay>() {
+            self.resources.insert(Recording::new(seed));
+        }
+
+        // The files of the previous run are no longer shown
+        self.resources.remove::<gui::RunReport>();
+
         // Create player
         let player_id = spawner::player(&mut self.world, (0, 0));
         self.resources.insert(Point::new(0, 0));
         self.resources.insert(player_id);
+        self.resources.insert(Dungeon::default());
+
+        // The run starts in the town above the dungeon
+        self.generate_map(0);
+    }
+
+    fn game_over_cleanup(&mut self) {
+        // Delete everything, the next game is created from the main menu
+        self.world.clear();
+        self.resources.remove::<Recording>();
+        self.resources.insert(RunSeed::random());
+    }
+
+    /// Runs a command accepted in an input state. The command is recorded, and when replaying
+    /// the game is checked to be in the same state as when the command was recorded
+    fn run_command(&mut self, runstate: RunState, command: Command) -> RunState {
+        let checkpoint = Checkpoint::new(&self.world, &self.resources);
+
+        if let Ok(mut replay) = self.resources.get_mut::<Replay>() {
+            if let Some(expected) = replay.expected.take() {
+                if expected != checkpoint {
+                    let report = format!("Replay diverged before command {} ({:?}): expected {:?}, found {:?}", replay.next, command, expected, checkpoint);
+                    replay.stop(report);
+                    return RunState::ReplayEnded;
+                }
+            }
+        }
+
+        match self.apply_command(runstate, command) {
+            None => {
+                if let Ok(mut replay) = self.resources.get_mut::<Replay>() {
+                    let report = format!("Replay diverged at command {}: {:?} cannot be used now", replay.next, command);
+                    replay.stop(report);
+                    return RunState::ReplayEnded;
+                }
+                runstate
+            }
+            Some(new_runstate) => {
+                if let Ok(mut recording) = self.resources.get_mut::<Recording>() {
+                    recording.record(checkpoint, command);
+                }
+                new_runstate
+            }
+        }
+    }
+
+    fn apply_command(&mut self, runstate: RunState, command: Command) -> Option<RunState> {
+        let player_id = *self.resources.get::<Entity>().unwrap();
+
+        match (runstate, command) {
+            (RunState::AwaitingInput, command) => player::apply_command(self, command),
+            (RunState::ShowInventory, Command::Cancel) => Some(RunState::AwaitingInput),
+            (RunState::ShowInventory, Command::SelectItem{index}) => {
+                let items = gui::inventory_items(&self.world, player_id);
+                items.get(index).map(|item| RunState::ShowItemActions{item: *item})
+            }
+            (RunState::ShowItemActions{..}, Command::Cancel) => Some(RunState::ShowInventory),
+            (RunState::ShowItemActions{item}, Command::UseItem) => {
+                let range = self.world.get::<Ranged>(item).map(|ranged| ranged.range);
+                match range {
+                    Ok(range) => Some(RunState::ShowTargeting{range, item}),
+                    Err(_) => {
+                        self.world.insert_one(player_id, WantsToUseItem {item, target: None}).unwrap();
+                        Some(RunState::PlayerTurn)
+                    }
+                }
+            }
+            (RunState::ShowItemActions{item}, Command::DropItem) => {
+                self.world.insert_one(player_id, WantsToDropItem {item}).unwrap();
+                Some(RunState::PlayerTurn)
+            }
+            (RunState::ShowItemActions{item}, Command::UnequipItem) => {
+                self.world.insert_one(player_id, WantsToUnequipItem{item}).unwrap();
+                Some(RunState::PlayerTurn)
+            }
+            (RunState::ShowTargeting{..}, Command::Cancel) => Some(RunState::AwaitingInput),
+            (RunState::ShowTargeting{item, ..}, Command::Target{x, y}) => {
+                self.world.insert_one(player_id, WantsToUseItem{item, target: Some(Point::new(x, y))}).unwrap();
+                Some(RunState::PlayerTurn)
+            }
+            _ => None
+        }
+    }
+
+    /// Advances the game by one state without drawing or reading input. The states waiting for the
+    /// player only move on when given a command, the menus are left to `tick`
+    fn advance(&mut self, runstate: RunState, command: Option<Command>) -> RunState {
+        match runstate {
+            RunState::PreRun => {
+                self.run_systems();
+                RunState::AwaitingInput
+            }
+            RunState::PlayerTurn => {
+                self.run_systems();
+                self.resources.get_mut::<RunStats>().unwrap().turns += 1;
+                RunState::MonsterTurn
+            }
+            RunState::MonsterTurn => {
+                self.run_systems();
+                RunState::AwaitingInput
+            }
+            RunState::NextLevel => {
+                self.next_level();
+                RunState::PreRun
+            }
+            RunState::PreviousLevel => {
+                self.previous_level();
+                RunState::PreRun
+            }
+            RunState::AwaitingInput | RunState::ShowInventory | RunState::ShowItemActions{..} | RunState::ShowTargeting{..} => {
+                match command {
+                    Some(command) => self.run_command(runstate, command),
+                    None => runstate
+                }
+            }
+            _ => runstate
+        }
+    }
+
+    /// Saving never stops the game, a failure is only reported in the log
+    fn log_save_error(&mut self, result: Result<(), saveload_system::SaveError>) {
+        if let Err(e) = result {
+            self.resources.get_mut::<GameLog>().unwrap().messages.push(e.to_string());
+        }
+    }
+
+    /// Writes the replay of the run, and adds where it went to the report of the run
+    fn write_replay(&mut self) {
+        let message = match self.resources.get::<Recording>() {
+            Err(_) => return,
+            Ok(recording) => match recording.write() {
+                Ok(path) => format!("Replay written to {}", path.display()),
+                Err(e) => format!("Unable to write replay: {}", e)
+            }
+        };
 
-        // Generate new map
-        self.generate_map(1);
+        if self.resources.contains::<gui::RunReport>() {
+            self.resources.get_mut::<gui::RunReport>().unwrap().messages.push(message);
+        } else {
+            self.resources.insert(gui::RunReport{messages: vec![message]});
+        }
     }
 }
 
 impl GameState for State {
     fn tick(&mut self, ctx: &mut Rltk) {
         ctx.cls();
-        particle_system::update_particles(&mut self.world, &mut self.resources, ctx);
+        particle_system::update_particles(&mut self.world, &mut self.resources, ctx.frame_time_ms);
 
         let mut new_runstate: RunState = *self.resources.get::<RunState>().unwrap();
 
+        // While replaying, commands come from the replay and keys only control the playback
+        let mut replay_command: Option<Command> = None;
+        if let Ok(mut replay) = self.resources.get_mut::<Replay>() {
+            if new_runstate != RunState::ReplayEnded {
+                replay.playback_input(ctx.key);
+                ctx.key = None;
+                ctx.left_click = false;
+            }
+
+            let waiting_for_input = matches!(new_runstate, RunState::AwaitingInput | RunState::ShowInventory | RunState::ShowItemActions{..} | RunState::ShowTargeting{..});
+            if waiting_for_input && replay.is_finished() {
+                let nb_commands = replay.recording.commands.len();
+                replay.stop(format!("Replay finished, all {} commands were played", nb_commands));
+            }
+            if new_runstate == RunState::GameOver {
+                let report = format!("The player died after {} of {} commands", replay.next, replay.recording.commands.len());
+                replay.stop(report);
+            }
+            if replay.report.is_some() {
+                new_runstate = RunState::ReplayEnded;
+            } else if waiting_for_input {
+                replay_command = replay.next_command(ctx.frame_time_ms);
+            }
+        }
+
         match new_runstate {
             RunState::MainMenu{..} => {}
+            RunState::SaveSlots{..} => {}
+            RunState::EnterSeed => {}
             RunState::GameOver => {}
+            RunState::HighScores => {}
+            RunState::ReplayEnded => {}
             _ => {
-                map::draw_map(&self.resources.get::<Map>().unwrap(), ctx);
-
                 {
                     let map = self.resources.get::<Map>().unwrap();
+                    let camera = Camera::centered_on(&map, *self.resources.get::<Point>().unwrap());
+                    map::draw_map(&map, &camera, ctx);
 
                     let mut query = self.world.query::<(&Position, &Renderable)>();
                     let mut to_render = query.iter().collect::<Vec<_>>();
                     to_render.sort_by_key(|a| a.1.1.order);
 
                     for (_id, (pos, render)) in to_render {
+                        let screen_pos = match camera.map_to_screen(pos.x, pos.y) {
+                            Some(screen_pos) => screen_pos,
+                            None => continue
+                        };
                         let idx = map.xy_idx(pos.x, pos.y);
                         if (idx < map.tiles.len() && render.render && map.visible_tiles[idx]) || render.always_render {
-                            ctx.set(pos.x + map::OFFSET_X as i32, pos.y + map::OFFSET_Y as i32, render.fg, render.bg, render.glyph);
+                            ctx.set(screen_pos.x, screen_pos.y, render.fg, render.bg, render.glyph);
                         }
                     }
 
@@ -212,120 +511,181 @@ impl GameState for State {
         }
 
         match new_runstate {
-            RunState::PreRun => {
-                self.run_systems();
-                new_runstate = RunState::AwaitingInput;
+            RunState::PreRun | RunState::PlayerTurn | RunState::MonsterTurn | RunState::PreviousLevel => {
+                new_runstate = self.advance(new_runstate, None);
             }
-            RunState::AwaitingInput => {
-                new_runstate = player::player_input(self, ctx);
-            }
-            RunState::PlayerTurn => {
-                self.run_systems();
-                new_runstate = RunState::MonsterTurn;
+            RunState::NextLevel => {
+                new_runstate = self.advance(new_runstate, None);
+                if !self.resources.contains::<Replay>() {
+                    let result = saveload_system::autosave(&self.world, &self.resources);
+                    self.log_save_error(result);
+                }
             }
-            RunState::MonsterTurn => {
-                self.run_systems();
-                new_runstate = RunState::AwaitingInput;
+            RunState::AwaitingInput => {
+                if ctx.key == Some(VirtualKeyCode::Escape) {
+                    new_runstate = RunState::SaveGame;
+                } else {
+                    new_runstate = self.advance(new_runstate, replay_command.or_else(|| player::player_input(ctx)));
+                }
             }
             RunState::ShowInventory => {
                 let result = gui::show_inventory(&mut self.world, &mut self.resources, ctx);
-                match result.0 {
-                    gui::ItemMenuResult::NoResponse => {}
-                    gui::ItemMenuResult::Cancel => { new_runstate = RunState::AwaitingInput }
+                let live_command = match result.0 {
+                    gui::ItemMenuResult::NoResponse => None,
+                    gui::ItemMenuResult::Cancel => Some(Command::Cancel),
                     gui::ItemMenuResult::Selected => {
-                        new_runstate = RunState::ShowItemActions{ item: result.1.unwrap() }
+                        let player_id = *self.resources.get::<Entity>().unwrap();
+                        let items = gui::inventory_items(&self.world, player_id);
+                        items.iter().position(|item| Some(*item) == result.1).map(|index| Command::SelectItem{index})
                     }
-                }
+                };
+                new_runstate = self.advance(new_runstate, replay_command.or(live_command));
             }
             RunState::ShowItemActions{item} => {
                 let result = gui::show_item_actions(&mut self.world, &mut self.resources, item, ctx);
+                let live_command = match result {
+                    gui::ItemActionSelection::NoSelection => None,
+                    gui::ItemActionSelection::Used => Some(Command::UseItem),
+                    gui::ItemActionSelection::Dropped => Some(Command::DropItem),
+                    gui::ItemActionSelection::Unequipped => Some(Command::UnequipItem),
+                    gui::ItemActionSelection::Cancel => Some(Command::Cancel)
+                };
+                new_runstate = self.advance(new_runstate, replay_command.or(live_command));
+            }
+            RunState::ShowTargeting{range, ..} => {
+                let res = gui::ranged_target(&mut self.world, &mut self.resources, ctx, range);
+                let live_command = match res.0 {
+                    gui::ItemMenuResult::Cancel => Some(Command::Cancel),
+                    gui::ItemMenuResult::NoResponse => None,
+                    gui::ItemMenuResult::Selected => res.1.map(|target| Command::Target{x: target.x, y: target.y})
+                };
+                new_runstate = self.advance(new_runstate, replay_command.or(live_command));
+            }
+            RunState::MainMenu{..} => {
+                let result = gui::main_menu(&mut self.world, &mut self.resources, ctx);
                 match result {
-                    gui::ItemActionSelection::NoSelection => {}
-                    gui::ItemActionSelection::Used => {
-                        let mut to_add_wants_use_item: Vec<Entity> = Vec::new();
-                        {
-                            let player_id = self.resources.get::<Entity>().unwrap();
-                            let is_item_ranged = self.world.get::<Ranged>(item);
-                            match is_item_ranged {
-                                Ok(is_item_ranged) => {
-                                    new_runstate = RunState::ShowTargeting{range:is_item_ranged.range, item};
+                    gui::MainMenuResult::NoSelection{selected} => {new_runstate = RunState::MainMenu{menu_selection: selected}}
+                    gui::MainMenuResult::Selection{selected} => {
+                        match selected {
+                            gui::MainMenuSelection::Continue => {
+                                if saveload_system::does_autosave_exist() {
+                                    match saveload_system::load_autosave(&mut self.world, &mut self.resources) {
+                                        Ok(()) => {new_runstate = RunState::PreRun}
+                                        Err(e) => {
+                                            self.resources.insert(e);
+                                            new_runstate = RunState::MainMenu{menu_selection: gui::MainMenuSelection::Continue};
+                                        }
+                                    }
                                 }
-                                Err(_) => {
-                                    to_add_wants_use_item.push(*player_id);
-                                    new_runstate = RunState::PlayerTurn;
+                            }
+                            gui::MainMenuSelection::NewGame => {
+                                new_runstate = self.show_save_slots(gui::SaveSlotMode::NewGame);
+                            }
+                            gui::MainMenuSelection::Seed => {
+                                self.resources.insert(gui::SeedInput{text: String::new()});
+                                new_runstate = RunState::EnterSeed;
+                            }
+                            gui::MainMenuSelection::LoadGame => {
+                                if saveload_system::does_save_exist() {
+                                    new_runstate = self.show_save_slots(gui::SaveSlotMode::LoadGame);
                                 }
                             }
+                            gui::MainMenuSelection::HighScores => {
+                                self.resources.insert(highscores::read_high_scores());
+                                new_runstate = RunState::HighScores;
+                            }
+                            gui::MainMenuSelection::Exit => {::std::process::exit(0)}
                         }
-
-                        for id in to_add_wants_use_item.iter() {
-                            self.world.insert_one(*id, WantsToUseItem {item, target: None}).unwrap();
-                        }
-                    }
-                    gui::ItemActionSelection::Dropped => {
-                        let player_id = self.resources.get::<Entity>().unwrap();
-                        self.world.insert_one(*player_id, WantsToDropItem {item}).unwrap();
-                        new_runstate = RunState::PlayerTurn;
-                    }
-                    gui::ItemActionSelection::Unequipped => {
-                        let player_id = self.resources.get::<Entity>().unwrap();
-                        self.world.insert_one(*player_id, WantsToUnequipItem{item}).unwrap();
-                        new_runstate = RunState::PlayerTurn;
                     }
-                    gui::ItemActionSelection::Cancel => { new_runstate = RunState::ShowInventory}
                 }
             }
-            RunState::ShowTargeting{range, item} => {
-                let res = gui::ranged_target(&mut self.world, &mut self.resources, ctx, range);
-                match res.0 {
-                    gui::ItemMenuResult::Cancel => new_runstate = RunState::AwaitingInput,
-                    gui::ItemMenuResult::NoResponse => {},
-                    gui::ItemMenuResult::Selected => {
-                        let player_id = self.resources.get::<Entity>().unwrap();
-                        self.world.insert_one(*player_id, WantsToUseItem{item, target: res.1}).unwrap();
-                        new_runstate = RunState::PlayerTurn;
+            RunState::EnterSeed => {
+                let result = gui::enter_seed(&mut self.resources, ctx);
+                match result {
+                    gui::EnterSeedResult::NoResponse => {}
+                    gui::EnterSeedResult::Cancel => {new_runstate = RunState::MainMenu{menu_selection: gui::MainMenuSelection::Seed}}
+                    gui::EnterSeedResult::Selected{seed} => {
+                        self.resources.insert(RunSeed(seed));
+                        new_runstate = RunState::MainMenu{menu_selection: gui::MainMenuSelection::NewGame};
                     }
                 }
             }
-            RunState::MainMenu{..} => {
-                let result = gui::main_menu(&mut self.world, &mut self.resources, ctx);
+            RunState::SaveSlots{mode, ..} => {
+                let result = gui::save_slots(&mut self.resources, ctx);
                 match result {
-                    gui::MainMenuResult::NoSelection{selected} => {new_runstate = RunState::MainMenu{menu_selection: selected}}
-                    gui::MainMenuResult::Selection{selected} => {
-                        match selected {
-                            gui::MainMenuSelection::NewGame => {new_runstate = RunState::MapGenAnimation}
-                            gui::MainMenuSelection::LoadGame => {new_runstate = RunState::PreRun}
-                            gui::MainMenuSelection::Exit => {::std::process::exit(0)}
+                    gui::SaveSlotResult::NoSelection{selected, confirm} => {new_runstate = RunState::SaveSlots{mode, selected, confirm}}
+                    gui::SaveSlotResult::Cancel => {
+                        let menu_selection = match mode {
+                            gui::SaveSlotMode::NewGame => gui::MainMenuSelection::NewGame,
+                            gui::SaveSlotMode::LoadGame => gui::MainMenuSelection::LoadGame
+                        };
+                        new_runstate = RunState::MainMenu{menu_selection};
+                    }
+                    gui::SaveSlotResult::Selected{slot} => {
+                        match mode {
+                            gui::SaveSlotMode::NewGame => {
+                                self.resources.insert(saveload_system::SaveSlot(slot));
+                                self.new_game();
+                                new_runstate = RunState::MapGenAnimation;
+                            }
+                            gui::SaveSlotMode::LoadGame => {
+                                match saveload_system::load_game(&mut self.world, &mut self.resources, slot) {
+                                    Ok(()) => {new_runstate = RunState::PreRun}
+                                    Err(e) => {
+                                        self.resources.insert(e);
+                                        new_runstate = RunState::MainMenu{menu_selection: gui::MainMenuSelection::LoadGame};
+                                    }
+                                }
+                            }
                         }
                     }
+                    gui::SaveSlotResult::Delete{slot} => {
+                        let result = saveload_system::delete_save(slot);
+                        self.log_save_error(result);
+                        self.resources.insert(saveload_system::read_save_slots());
+                        new_runstate = RunState::SaveSlots{mode, selected: slot, confirm: None};
+                    }
                 }
             }
             RunState::SaveGame => {
-                /*
-                let data = serde_json::to_string(&*self.resources.get::<Map>().unwrap()).unwrap();
-                println!("{}", data);
-    
-                let c: Context;
-                let mut writer = Vec::with_capacity(128);
-                let s = serde_json::Serializer::new(writer);
-                hecs::serialize::row::serialize(&self.world, &mut c, s);
-
-                for (id, _s) in self.world.query_mut::<&SerializeMe>() {
-                    println!("{:?}", id);
+                match saveload_system::save_game(&self.world, &self.resources) {
+                    Ok(()) => {
+                        let result = saveload_system::delete_autosave();
+                        self.log_save_error(result);
+                        // Only the replay is written for a run that goes on later
+                        self.resources.remove::<gui::RunReport>();
+                        self.write_replay();
+                        self.game_over_cleanup();
+                        new_runstate = RunState::MainMenu{menu_selection: gui::MainMenuSelection::LoadGame};
+                    }
+                    Err(e) => {
+                        // Keep playing rather than losing the run
+                        self.log_save_error(Err(e));
+                        new_runstate = RunState::AwaitingInput;
+                    }
                 }
-                */
-                println!("Saving game... TODO");
-                self.game_over_cleanup();
-                new_runstate = RunState::MainMenu{menu_selection: gui::MainMenuSelection::LoadGame};
             }
-            RunState::NextLevel => {
-                self.next_level();
-                new_runstate = RunState::PreRun;
+            RunState::HighScores => {
+                if gui::high_scores(&self.resources, ctx) {
+                    new_runstate = RunState::MainMenu{menu_selection: gui::MainMenuSelection::HighScores};
+                }
+            }
+            RunState::ReplayEnded => {
+                if gui::replay_report(&self.resources, ctx) {
+                    self.resources.remove::<Replay>();
+                    self.game_over_cleanup();
+                    new_runstate = RunState::MainMenu{menu_selection: gui::MainMenuSelection::NewGame};
+                }
             }
             RunState::GameOver => {
-                let result = gui::game_over(ctx);
+                let result = gui::game_over(&self.resources, ctx);
                 match result {
                     gui::GameOverResult::NoSelection => {}
                     gui::GameOverResult::QuitToMenu => {
+                        self.write_replay();
+                        let slot = self.resources.get::<saveload_system::SaveSlot>().unwrap().0;
+                        let result = saveload_system::delete_save(slot).and(saveload_system::delete_autosave());
+                        self.log_save_error(result);
                         self.game_over_cleanup();
                         new_runstate = RunState::MainMenu {menu_selection: gui::MainMenuSelection::NewGame};
                     }
@@ -336,7 +696,9 @@ impl GameState for State {
                     new_runstate = RunState::PreRun;
                 }
                 ctx.cls();
-                map::draw_map(&self.mapgen_data.history[self.mapgen_data.index], ctx);
+                let snapshot = &self.mapgen_data.history[self.mapgen_data.index];
+                let camera = Camera::centered_on(snapshot, Point::new(snapshot.width / 2, snapshot.height / 2));
+                map::draw_map(snapshot, &camera, ctx);
 
                 self.mapgen_data.timer += ctx.frame_time_ms;
                 if self.mapgen_data.timer > MAPGEN_FRAME_TIME {
@@ -349,37 +711,79 @@ impl GameState for State {
             }
         }
 
-        self.resources.insert::<RunState>(new_runstate).unwrap();
+        let previous_runstate = self.resources.insert::<RunState>(new_runstate).unwrap();
+
+        // Saves are only written and deleted outside of the main menu
+        if matches!(new_runstate, RunState::MainMenu{..}) && !matches!(previous_runstate, RunState::MainMenu{..}) {
+            self.resources.insert(saveload_system::SaveFiles::scan());
+        }
 
         damage_system::delete_the_dead(&mut self.world, &mut self.resources);
 
+        // Keep a record of the run as soon as the player dies, a replayed death was already recorded
+        let player_died = new_runstate != RunState::GameOver && *self.resources.get::<RunState>().unwrap() == RunState::GameOver;
+        if player_died && !self.resources.contains::<Replay>() {
+            let morgue_message = match morgue::write_morgue(&self.world, &self.resources) {
+                Ok(path) => format!("Morgue file written to {}", path.display()),
+                Err(e) => format!("Unable to write morgue file: {}", e)
+            };
+            let mut messages = vec![morgue_message];
+            match highscores::record_run(&self.world, &self.resources) {
+                Ok(table) => { self.resources.insert(table); }
+                Err(e) => {
+                    messages.push(format!("Unable to write high scores: {}", e));
+                    self.resources.insert(highscores::read_high_scores());
+                }
+            }
+            self.resources.insert(gui::RunReport{messages});
+        }
+
     }
 }
 
 fn main() -> rltk::BError {
+    let args: Vec<String> = std::env::args().collect();
+
+    // Play random commands without a window with: roguelike --simulate <turns> [--seed <seed>]
+    if let Some(i) = args.iter().position(|arg| arg == "--simulate") {
+        let nb_turns = args.get(i + 1).and_then(|turns| turns.parse::<u32>().ok()).unwrap_or(1000);
+        let seed = args.iter().position(|arg| arg == "--seed")
+            .and_then(|i| args.get(i + 1))
+            .and_then(|seed| seed.parse::<u64>().ok())
+            .map(RunSeed)
+            .unwrap_or_else(RunSeed::random);
+        println!("{}", headless::simulate(seed, nb_turns));
+        return Ok(());
+    }
+
     let context = RltkBuilder::simple(80, 50).unwrap()
         .with_tile_dimensions(12, 12)
         .with_title("Roguelike")
         .build()?;
 
-    let mut gs = State {
-        world: World::new(),
-        resources: Resources::default(),
-        mapgen_data: MapGenData{history: Vec::new(), timer: 0.0, index: 0}
-    };
+    let mut gs = State::new();
 
-    gs.resources.insert(Map::new(1));
-    gs.resources.insert(Point::new(0, 0));
-    gs.resources.insert(rltk::RandomNumberGenerator::new());
-
-    let player_id = spawner::player(&mut gs.world, (0, 0));
-    gs.resources.insert(player_id);
-
-    gs.resources.insert(RunState::MainMenu{menu_selection: gui::MainMenuSelection::NewGame});
-    gs.resources.insert(gamelog::GameLog{messages: vec!["Welcome to the roguelike!".to_string()]});
-    gs.resources.insert(particle_system::ParticleBuilder::new());
-
-    gs.generate_map(1);
+    // Replay a recorded run with: roguelike --replay <file> [--speed <commands per second>]
+    if let Some(i) = args.iter().position(|arg| arg == "--replay") {
+        let path = args.get(i + 1).map(|path| path.as_str()).unwrap_or("");
+        let recording = match Recording::read(path) {
+            Ok(recording) => recording,
+            Err(e) => {
+                eprintln!("{}", e);
+                ::std::process::exit(1);
+            }
+        };
+        let speed = args.iter().position(|arg| arg == "--speed")
+            .and_then(|i| args.get(i + 1))
+            .and_then(|speed| speed.parse::<f32>().ok())
+            .filter(|speed| *speed > 0.0)
+            .unwrap_or(replay::DEFAULT_REPLAY_SPEED);
+
+        gs.resources.insert(recording.seed);
+        gs.resources.insert(Replay::new(recording, speed));
+        gs.new_game();
+        gs.resources.insert(RunState::PreRun);
+    }
 
     rltk::main_loop(context, gs)
 }
@@ -1,20 +1,74 @@
-use serde;
 use serde::{Serialize, Deserialize};
 use hecs::*;
-use rltk;
 use rltk::{Rltk, Algorithm2D, BaseMap, Point};
 use crate::Palette;
+use crate::camera::Camera;
+use crate::rect::Rect;
 
 
-pub const MAPWIDTH: usize = 80;
-pub const MAPHEIGHT: usize = 40;
-pub const MAPCOUNT: usize = MAPWIDTH * MAPHEIGHT;
-pub const OFFSET_X: usize = 0;
-pub const OFFSET_Y: usize = 11;
+/// Size of the levels, maps of any size can be drawn through the camera
+pub const MAPWIDTH: i32 = 80;
+pub const MAPHEIGHT: i32 = 40;
 
 #[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
 pub enum TileType {
-    Wall, Floor, StairsDown, StairsUp
+    Wall, Floor, StairsDown, StairsUp, Grass, ShallowWater, DeepWater, Lava, Bridge
+}
+
+impl TileType {
+    /// Deep water blocks every mover alike, the player, monsters and NPCs: nothing in the game
+    /// can swim
+    pub fn is_walkable(self) -> bool {
+        !matches!(self, TileType::Wall | TileType::DeepWater)
+    }
+
+    pub fn is_opaque(self) -> bool {
+        self == TileType::Wall
+    }
+
+    /// Ground monsters and items can be spawned on
+    pub fn is_open_ground(self) -> bool {
+        matches!(self, TileType::Floor | TileType::Grass)
+    }
+
+    /// Walking on the tile hurts, monsters never step on it
+    pub fn is_hazard(self) -> bool {
+        self.hazard_name().is_some()
+    }
+
+    /// What hurts whoever stands on the tile, shown in the log and in the morgue file
+    pub fn hazard_name(self) -> Option<&'static str> {
+        match self {
+            TileType::Lava => Some("lava"),
+            _ => None
+        }
+    }
+
+    /// Multiplies the cost of the steps onto the tile when looking for a path
+    pub fn cost(self) -> f32 {
+        match self {
+            TileType::ShallowWater => 2.0,
+            _ => 1.0
+        }
+    }
+}
+
+/// What a room of a level was furnished as, it decides what is found in it
+#[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum RoomTheme {
+    TreasureVault, MonsterDen, Armory, Library, Shrine
+}
+
+impl RoomTheme {
+    pub fn name(self) -> &'static str {
+        match self {
+            RoomTheme::TreasureVault => "Treasure vault",
+            RoomTheme::MonsterDen => "Monster den",
+            RoomTheme::Armory => "Armory",
+            RoomTheme::Library => "Library",
+            RoomTheme::Shrine => "Shrine"
+        }
+    }
 }
 
 #[derive(Default, Serialize, Deserialize, Clone)]
@@ -26,6 +80,11 @@ pub struct Map {
     pub visible_tiles: Vec<bool>,
     pub blocked: Vec<bool>,
     pub depth: i32,
+    pub room_themes: Vec<(Rect, RoomTheme)>,
+
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    pub view_blocked: Vec<bool>,
 
     #[serde(skip_serializing)]
     #[serde(skip_deserializing)]
@@ -33,19 +92,27 @@ pub struct Map {
 }
 
 impl Map {
-    pub fn new(new_depth: i32) -> Map {
+    pub fn new(new_depth: i32, width: i32, height: i32) -> Map {
+        let map_count = (width * height) as usize;
         Map {
-            tiles: vec![TileType::Wall; MAPCOUNT],
-            width: MAPWIDTH as i32,
-            height: MAPHEIGHT as i32,
-            revealed_tiles: vec![false; MAPCOUNT],
-            visible_tiles: vec![false; MAPCOUNT],
-            blocked: vec![false; MAPCOUNT],
-            tile_content: vec![Vec::new(); MAPCOUNT],
-            depth: new_depth
+            tiles: vec![TileType::Wall; map_count],
+            width,
+            height,
+            revealed_tiles: vec![false; map_count],
+            visible_tiles: vec![false; map_count],
+            blocked: vec![false; map_count],
+            view_blocked: vec![false; map_count],
+            tile_content: vec![Vec::new(); map_count],
+            depth: new_depth,
+            room_themes: Vec::new()
         }
     }
 
+    /// Theme of the room the tile is in, None outside of the themed rooms
+    pub fn room_theme(&self, x: i32, y: i32) -> Option<RoomTheme> {
+        self.room_themes.iter().find(|(room, _theme)| room.contains(x, y)).map(|(_room, theme)| *theme)
+    }
+
     pub fn set_tile(&mut self, x: i32, y: i32, value: TileType) {
         let idx = self.xy_idx(x, y);
         self.tiles[idx] = value;
@@ -59,6 +126,14 @@ impl Map {
         (idx as i32 % self.width, idx as i32 / self.width)
     }
 
+    /// The walkable tile closest to a position, used when a level has no stairs to arrive on
+    pub fn closest_walkable_tile(&self, x: i32, y: i32) -> Option<(i32, i32)> {
+        (0..self.tiles.len())
+            .filter(|idx| self.tiles[*idx].is_walkable())
+            .map(|idx| self.idx_xy(idx))
+            .min_by_key(|(tile_x, tile_y)| (tile_x - x).pow(2) + (tile_y - y).pow(2))
+    }
+
     pub fn is_wall(&self, x: i32, y: i32) -> bool {
         let idx = self.xy_idx(x, y);
         self.tiles[idx] == TileType::Wall
@@ -66,7 +141,7 @@ impl Map {
 
     pub fn set_blocked(&mut self) {
         for (i, t) in self.tiles.iter_mut().enumerate() {
-            self.blocked[i] = *t == TileType::Wall;
+            self.blocked[i] = !t.is_walkable();
         }
     }
     
@@ -76,12 +151,10 @@ impl Map {
         }
     }
 
-    pub fn transform_mouse_pos(&self, mouse_pos: (i32, i32)) -> (i32, i32) {
-        (mouse_pos.0 - OFFSET_X as i32, mouse_pos.1 - OFFSET_Y as i32)
-    }
-
-    pub fn mouse_in_bounds(&self, mouse_pos: (i32, i32)) -> bool {
-        mouse_pos.0 >= 0  && mouse_pos.0 <= self.width && mouse_pos.1 >= 0 && mouse_pos.1 <= self.height
+    /// Rebuilds the lists that are not saved with the map, they are filled by the map indexing
+    pub fn reset_content(&mut self) {
+        self.view_blocked = vec![false; self.tiles.len()];
+        self.tile_content = vec![Vec::new(); self.tiles.len()];
     }
 
     fn is_exit_valid(&self, x:i32, y:i32) -> bool {
@@ -99,7 +172,7 @@ impl Algorithm2D for Map {
 
 impl BaseMap for Map {
     fn is_opaque(&self, idx: usize) -> bool {
-        self.tiles[idx] == TileType::Wall
+        self.tiles[idx].is_opaque() || self.view_blocked[idx]
     }
 
     fn get_pathing_distance(&self, idx1: usize, idx2: usize) -> f32 {
@@ -110,7 +183,7 @@ impl BaseMap for Map {
     }
 
     fn get_available_exits(&self, idx: usize) -> rltk::SmallVec<[(usize, f32); 10]> {
-        let mut exits = rltk::SmallVec::new();
+        let mut exits: rltk::SmallVec<[(usize, f32); 10]> = rltk::SmallVec::new();
         let (x, y) = self.idx_xy(idx);
         let w = self.width as usize;
 
@@ -124,18 +197,32 @@ impl BaseMap for Map {
         if self.is_exit_valid(x - 1, y + 1) { exits.push((idx + w - 1, 1.45)) };
         if self.is_exit_valid(x + 1, y + 1) { exits.push((idx + w + 1, 1.45)) };
 
+        for (exit_idx, cost) in exits.iter_mut() {
+            *cost *= self.tiles[*exit_idx].cost();
+        }
         exits
     }
 }
 
+// Rock buried in other walls is drawn as empty space, only the walls along the floor are outlined
+fn is_solid_rock(map: &Map, x: i32, y: i32) -> bool {
+    if x < 1 || x > map.width - 2 || y < 1 || y > map.height - 2 { return false }
+    (-1..=1).all(|dy| (-1..=1).all(|dx| map.is_wall(x + dx, y + dy)))
+}
+
+fn is_outlined_wall(map: &Map, x: i32, y: i32) -> bool {
+    map.is_wall(x, y) && !is_solid_rock(map, x, y)
+}
+
 fn wall_glyph(map: &Map, x: i32, y: i32) -> char {
     if x < 1 || x > map.width - 2 || y < 1 || y > map.height - 2 { return 'x' }
+    if is_solid_rock(map, x, y) { return ' ' }
     let mut mask: u8 = 0;
 
-    if map.is_wall(x, y - 1) { mask += 1 }
-    if map.is_wall(x, y + 1) { mask += 2 }
-    if map.is_wall(x - 1, y) { mask += 4 }
-    if map.is_wall(x + 1, y) { mask += 8 }
+    if is_outlined_wall(map, x, y - 1) { mask += 1 }
+    if is_outlined_wall(map, x, y + 1) { mask += 2 }
+    if is_outlined_wall(map, x - 1, y) { mask += 4 }
+    if is_outlined_wall(map, x + 1, y) { mask += 8 }
 
     match mask {
         0 => { '■' }
@@ -158,13 +245,17 @@ fn wall_glyph(map: &Map, x: i32, y: i32) -> char {
     }
 }
 
-pub fn draw_map(map: &Map, ctx : &mut Rltk) {
+pub fn draw_map(map: &Map, camera: &Camera, ctx : &mut Rltk) {
     for (idx, tile) in map.tiles.iter().enumerate() {
         if map.revealed_tiles[idx] {
+            let (x, y) = map.idx_xy(idx);
+            let screen_pos = match camera.map_to_screen(x, y) {
+                Some(screen_pos) => screen_pos,
+                None => continue
+            };
             let glyph;
             let mut fg;
             let mut bg;
-            let (x, y) = map.idx_xy(idx);
             match tile {
                 TileType::Floor => {
                     fg = Palette::COLOR_2;
@@ -174,7 +265,7 @@ pub fn draw_map(map: &Map, ctx : &mut Rltk) {
                 TileType::Wall => {
                     fg = Palette::MAIN_FG;
                     bg = Palette::MAIN_BG;
-                    glyph = rltk::to_cp437(wall_glyph(&map, x, y));
+                    glyph = rltk::to_cp437(wall_glyph(map, x, y));
                 }
                 TileType::StairsDown => {
                     fg = Palette::MAIN_FG;
@@ -186,12 +277,37 @@ pub fn draw_map(map: &Map, ctx : &mut Rltk) {
                     bg = Palette::MAIN_BG;
                     glyph = rltk::to_cp437('<');
                 }
+                TileType::Grass => {
+                    fg = Palette::GRASS;
+                    bg = Palette::MAIN_BG;
+                    glyph = rltk::to_cp437('"');
+                }
+                TileType::ShallowWater => {
+                    fg = Palette::WATER;
+                    bg = Palette::MAIN_BG;
+                    glyph = rltk::to_cp437('~');
+                }
+                TileType::DeepWater => {
+                    fg = Palette::WATER;
+                    bg = Palette::DEEP_WATER_BG;
+                    glyph = rltk::to_cp437('≈');
+                }
+                TileType::Lava => {
+                    fg = Palette::LAVA;
+                    bg = Palette::MAIN_BG;
+                    glyph = rltk::to_cp437('≈');
+                }
+                TileType::Bridge => {
+                    fg = Palette::COLOR_3;
+                    bg = Palette::MAIN_BG;
+                    glyph = rltk::to_cp437('=');
+                }
             }
             if !map.visible_tiles[idx] {
                 fg = fg.to_greyscale();
                 bg = bg.to_greyscale();
             }
-            ctx.set(x as usize + OFFSET_X, y as usize + OFFSET_Y, fg, bg, glyph);
+            ctx.set(screen_pos.x, screen_pos.y, fg, bg, glyph);
         }
     }
 }
@@ -0,0 +1,117 @@
+use rltk::RandomNumberGenerator;
+
+use crate::spawner;
+
+use super::{MetaMapBuilder, BuilderMap, generate_voronoi_spawn_regions, remove_unreachable_areas_returning_most_distant,
+            TileType, Position};
+
+const NB_SPAWN_REGIONS: usize = 12;
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum XStart {Left, Center, Right}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum YStart {Top, Center, Bottom}
+
+/// Starts the player on the floor closest to a point of the map
+pub struct AreaStartingPosition {
+    x: XStart,
+    y: YStart
+}
+
+impl MetaMapBuilder for AreaStartingPosition {
+    fn build_map(&mut self, _rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let map = &build_data.map;
+        let x = match self.x {
+            XStart::Left => 1,
+            XStart::Center => map.width / 2,
+            XStart::Right => map.width - 2
+        };
+        let y = match self.y {
+            YStart::Top => 1,
+            YStart::Center => map.height / 2,
+            YStart::Bottom => map.height - 2
+        };
+
+        let start = map.tiles.iter().enumerate()
+            .filter(|(_idx, tile)| **tile == TileType::Floor)
+            .map(|(idx, _tile)| map.idx_xy(idx))
+            .min_by_key(|(floor_x, floor_y)| (floor_x - x) * (floor_x - x) + (floor_y - y) * (floor_y - y))
+            .expect("No floor to start on");
+        build_data.starting_position = Some(Position{x: start.0, y: start.1});
+    }
+}
+
+impl AreaStartingPosition {
+    pub fn new(x: XStart, y: YStart) -> Box<AreaStartingPosition> {
+        Box::new(AreaStartingPosition{x, y})
+    }
+}
+
+/// Walls up the floor the player cannot reach from the start
+pub struct CullUnreachable {}
+
+impl MetaMapBuilder for CullUnreachable {
+    fn build_map(&mut self, _rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let start = build_data.starting_position.expect("Culling needs a starting position");
+        let start_idx = build_data.map.xy_idx(start.x, start.y);
+        remove_unreachable_areas_returning_most_distant(&mut build_data.map, start_idx);
+
+        let map = &build_data.map;
+        build_data.spawn_list.retain(|((x, y), _name)| map.tiles[map.xy_idx(*x, *y)].is_walkable());
+        build_data.take_snapshot();
+    }
+}
+
+impl CullUnreachable {
+    pub fn new() -> Box<CullUnreachable> {
+        Box::new(CullUnreachable{})
+    }
+}
+
+/// Puts the stairs on the reachable tile the furthest away from the start
+pub struct DistantExit {}
+
+impl MetaMapBuilder for DistantExit {
+    fn build_map(&mut self, _rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        for tile in build_data.map.tiles.iter_mut() {
+            if *tile == TileType::StairsDown { *tile = TileType::Floor; }
+        }
+
+        let start = build_data.starting_position.expect("Placing the stairs needs a starting position");
+        let start_idx = build_data.map.xy_idx(start.x, start.y);
+        let exit_idx = remove_unreachable_areas_returning_most_distant(&mut build_data.map, start_idx);
+        build_data.map.tiles[exit_idx] = TileType::StairsDown;
+        build_data.take_snapshot();
+    }
+}
+
+impl DistantExit {
+    pub fn new() -> Box<DistantExit> {
+        Box::new(DistantExit{})
+    }
+}
+
+/// Spawns monsters and items in areas of the floor grouped around random points, nothing spawns
+/// in the area of the start
+pub struct VoronoiSpawning {}
+
+impl MetaMapBuilder for VoronoiSpawning {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let start = build_data.starting_position.map(|start| (start.x, start.y));
+        let regions = generate_voronoi_spawn_regions(&build_data.map, rng, NB_SPAWN_REGIONS);
+        for region in regions.iter().filter(|region| start.is_none_or(|start| !region.contains(&start))) {
+            let area: Vec<(i32, i32)> = region.iter()
+                .filter(|point| !build_data.spawn_list.iter().any(|(spawn_point, _name)| spawn_point == *point))
+                .copied()
+                .collect();
+            spawner::spawn_region(rng, &area, build_data.map.depth, &build_data.spawn_table, &mut build_data.spawn_list);
+        }
+    }
+}
+
+impl VoronoiSpawning {
+    pub fn new() -> Box<VoronoiSpawning> {
+        Box::new(VoronoiSpawning{})
+    }
+}
@@ -0,0 +1,150 @@
+use rltk::RandomNumberGenerator;
+
+use crate::SHOW_MAPGEN_ANIMATION;
+
+use super::{InitialMapBuilder, BuilderMap, apply_room_to_map, apply_horizontal_corridor,
+            apply_vertical_corridor, Rect};
+
+const MIN_PARTITION_SIZE: i32 = 10;
+const MIN_ROOM_SIZE: i32 = 4;
+
+// Node of the partition tree, a leaf gets a room once the map is fully split
+struct Partition {
+    rect: Rect,
+    children: Option<(usize, usize)>,
+    room: Option<usize>
+}
+
+pub struct BspDungeonBuilder {}
+
+impl InitialMapBuilder for BspDungeonBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        self.build(rng, build_data);
+    }
+}
+
+impl BspDungeonBuilder {
+    pub fn new() -> Box<BspDungeonBuilder> {
+        Box::new(BspDungeonBuilder{})
+    }
+
+    fn build(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let mut rooms: Vec<Rect> = Vec::new();
+        build_data.take_snapshot();
+
+        // Split the partitions breadth first, so the animation shows the map being cut in halves
+        let mut partitions = vec![Partition{rect: Rect::new(1, 1, build_data.map.width - 2, build_data.map.height - 2), children: None, room: None}];
+        let mut next = 0;
+        while next < partitions.len() {
+            if let Some((first, second)) = split(rng, &partitions[next].rect) {
+                partitions[next].children = Some((partitions.len(), partitions.len() + 1));
+                partitions.push(Partition{rect: first, children: None, room: None});
+                partitions.push(Partition{rect: second, children: None, room: None});
+                take_partition_snapshot(build_data, &partitions);
+            }
+            next += 1;
+        }
+
+        // One room per leaf, with at least one wall between it and the neighbouring leaves
+        for partition in partitions.iter_mut().filter(|partition| partition.children.is_none()) {
+            let leaf = partition.rect;
+            let w = rng.range(MIN_ROOM_SIZE, leaf.width() - 1);
+            let h = rng.range(MIN_ROOM_SIZE, leaf.height() - 1);
+            let x = rng.range(leaf.x1 + 1, leaf.x2 - w);
+            let y = rng.range(leaf.y1 + 1, leaf.y2 - h);
+
+            let room = Rect::new(x, y, w, h);
+            apply_room_to_map(&mut build_data.map, &room);
+            partition.room = Some(rooms.len());
+            rooms.push(room);
+            build_data.take_snapshot();
+        }
+
+        // Connect the two halves of every split through their closest rooms, the deepest splits first
+        for partition in partitions.iter().rev() {
+            if let Some((first, second)) = partition.children {
+                let (room1, room2) = closest_rooms(&rooms, &leaf_rooms(&partitions, first), &leaf_rooms(&partitions, second));
+                let (x1, y1) = rooms[room1].center();
+                let (x2, y2) = rooms[room2].center();
+                if rng.roll_dice(1, 2) == 1 {
+                    apply_horizontal_corridor(&mut build_data.map, x1, x2, y1);
+                    apply_vertical_corridor(&mut build_data.map, x2, y1, y2);
+                } else {
+                    apply_vertical_corridor(&mut build_data.map, x1, y1, y2);
+                    apply_horizontal_corridor(&mut build_data.map, x1, x2, y2);
+                }
+                build_data.take_snapshot();
+            }
+        }
+
+        build_data.rooms = Some(rooms);
+    }
+}
+
+// Shows the current leaves as open areas separated by the walls of the splits
+fn take_partition_snapshot(build_data: &mut BuilderMap, partitions: &[Partition]) {
+    if SHOW_MAPGEN_ANIMATION {
+        let mut snapshot = build_data.map.clone();
+        for partition in partitions.iter().filter(|partition| partition.children.is_none()) {
+            let rect = partition.rect;
+            apply_room_to_map(&mut snapshot, &Rect::new(rect.x1 + 1, rect.y1 + 1, rect.width() - 1, rect.height() - 1));
+        }
+        for revealed in snapshot.revealed_tiles.iter_mut() {
+            *revealed = true;
+        }
+        build_data.history.push(snapshot);
+    }
+}
+
+// Cuts a partition in two along its longest side, None if it is too small to hold two rooms
+fn split(rng: &mut RandomNumberGenerator, rect: &Rect) -> Option<(Rect, Rect)> {
+    let can_split_x = rect.width() >= MIN_PARTITION_SIZE * 2;
+    let can_split_y = rect.height() >= MIN_PARTITION_SIZE * 2;
+
+    let split_x = match (can_split_x, can_split_y) {
+        (false, false) => return None,
+        (true, false) => true,
+        (false, true) => false,
+        (true, true) => {
+            if rect.width() * 4 > rect.height() * 5 { true }
+            else if rect.height() * 4 > rect.width() * 5 { false }
+            else { rng.roll_dice(1, 2) == 1 }
+        }
+    };
+
+    if split_x {
+        let w = rng.range(MIN_PARTITION_SIZE, rect.width() - MIN_PARTITION_SIZE + 1);
+        Some((Rect::new(rect.x1, rect.y1, w, rect.height()), Rect::new(rect.x1 + w, rect.y1, rect.width() - w, rect.height())))
+    } else {
+        let h = rng.range(MIN_PARTITION_SIZE, rect.height() - MIN_PARTITION_SIZE + 1);
+        Some((Rect::new(rect.x1, rect.y1, rect.width(), h), Rect::new(rect.x1, rect.y1 + h, rect.width(), rect.height() - h)))
+    }
+}
+
+fn leaf_rooms(partitions: &[Partition], node: usize) -> Vec<usize> {
+    match partitions[node].children {
+        None => partitions[node].room.into_iter().collect(),
+        Some((first, second)) => {
+            let mut rooms = leaf_rooms(partitions, first);
+            rooms.extend(leaf_rooms(partitions, second));
+            rooms
+        }
+    }
+}
+
+fn closest_rooms(rooms: &[Rect], first: &[usize], second: &[usize]) -> (usize, usize) {
+    let mut closest = (first[0], second[0]);
+    let mut closest_distance = i32::MAX;
+    for room1 in first.iter() {
+        for room2 in second.iter() {
+            let (x1, y1) = rooms[*room1].center();
+            let (x2, y2) = rooms[*room2].center();
+            let distance = (x1 - x2).abs() + (y1 - y2).abs();
+            if distance < closest_distance {
+                closest = (*room1, *room2);
+                closest_distance = distance;
+            }
+        }
+    }
+    closest
+}
@@ -0,0 +1,50 @@
+use rltk::RandomNumberGenerator;
+
+use super::{InitialMapBuilder, BuilderMap, TileType};
+
+const FLOOR_CHANCE: i32 = 45;
+const NB_ITERATIONS: i32 = 15;
+
+pub struct CellularAutomataBuilder {}
+
+impl InitialMapBuilder for CellularAutomataBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        self.build(rng, build_data);
+    }
+}
+
+impl CellularAutomataBuilder {
+    pub fn new() -> Box<CellularAutomataBuilder> {
+        Box::new(CellularAutomataBuilder{})
+    }
+
+    fn build(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        // Random noise, the border always stays a wall
+        for y in 1..build_data.map.height - 1 {
+            for x in 1..build_data.map.width - 1 {
+                let tile = if rng.roll_dice(1, 100) <= FLOOR_CHANCE { TileType::Floor } else { TileType::Wall };
+                build_data.map.set_tile(x, y, tile);
+            }
+        }
+        build_data.take_snapshot();
+
+        // Smooth the noise into caves, a tile becomes a wall when surrounded by walls or isolated
+        for _ in 0..NB_ITERATIONS {
+            let map = &mut build_data.map;
+            let mut new_tiles = map.tiles.clone();
+            for y in 1..map.height - 1 {
+                for x in 1..map.width - 1 {
+                    let mut neighbor_walls = 0;
+                    for (dx, dy) in [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)] {
+                        if map.is_wall(x + dx, y + dy) { neighbor_walls += 1; }
+                    }
+
+                    let idx = map.xy_idx(x, y);
+                    new_tiles[idx] = if neighbor_walls > 4 || neighbor_walls == 0 { TileType::Wall } else { TileType::Floor };
+                }
+            }
+            map.tiles = new_tiles;
+            build_data.take_snapshot();
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use super::{Map, Rect, TileType};
+use rltk::{DijkstraMap, RandomNumberGenerator};
 use std::cmp;
 
 
@@ -23,22 +24,77 @@ pub fn apply_horizontal_corridor(map: &mut Map, x1: i32, x2:i32, y: i32) {
 }
 
 
-pub fn remove_useless_walls(map: &mut Map) {
-    let mut to_remove: Vec<(i32, i32)> = Vec::new();
+/// Turns the ground the start cannot reach into walls, and returns the reachable floor tile
+/// that is the furthest away from the start
+pub fn remove_unreachable_areas_returning_most_distant(map: &mut Map, start_idx: usize) -> usize {
+    map.set_blocked();
+    let dijkstra_map = DijkstraMap::new(map.width as usize, map.height as usize, &[start_idx], &*map, map.tiles.len() as f32);
 
-    for i in 0..map.tiles.len() {
-        let (x, y) = map.idx_xy(i);
+    let mut exit_tile = (start_idx, 0.0f32);
+    for (idx, tile) in map.tiles.iter_mut().enumerate() {
+        if !tile.is_walkable() { continue }
+        let distance = dijkstra_map.map[idx];
+        if distance == f32::MAX {
+            *tile = TileType::Wall;
+        } else if *tile == TileType::Floor && distance > exit_tile.1 {
+            exit_tile = (idx, distance);
+        }
+    }
+    map.set_blocked();
+
+    exit_tile.0
+}
 
-        if x < 1 || x > map.width - 2 || y < 1 || y > map.height - 2 { continue }
+/// Groups the floor tiles into areas around random seed points, to spawn entities on maps
+/// that have no rooms
+pub fn generate_voronoi_spawn_regions(map: &Map, rng: &mut RandomNumberGenerator, nb_regions: usize) -> Vec<Vec<(i32, i32)>> {
+    let floor: Vec<(i32, i32)> = map.tiles.iter().enumerate()
+        .filter(|(_idx, tile)| tile.is_open_ground())
+        .map(|(idx, _tile)| map.idx_xy(idx))
+        .collect();
+    if floor.is_empty() { return Vec::new() }
 
-        if map.is_wall(x - 1, y - 1) && map.is_wall(x, y - 1) && map.is_wall(x + 1, y - 1) &&
-           map.is_wall(x - 1, y    ) && map.is_wall(x, y    ) && map.is_wall(x + 1, y    ) &&
-           map.is_wall(x - 1, y + 1) && map.is_wall(x, y + 1) && map.is_wall(x + 1, y + 1) {
-            to_remove.push((x, y));
-        }
+    let seeds: Vec<(i32, i32)> = (0..nb_regions).map(|_| floor[rng.range(0, floor.len() as i32) as usize]).collect();
+    let mut regions: Vec<Vec<(i32, i32)>> = vec![Vec::new(); nb_regions];
+    for (x, y) in floor {
+        let closest_seed = seeds.iter().enumerate()
+            .min_by_key(|(_i, (seed_x, seed_y))| (x - seed_x) * (x - seed_x) + (y - seed_y) * (y - seed_y))
+            .map(|(i, _seed)| i)
+            .unwrap();
+        regions[closest_seed].push((x, y));
     }
 
-    for (x, y) in to_remove {
-        map.set_tile(x, y, TileType::Floor);
+    regions.retain(|region| !region.is_empty());
+    regions
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum Symmetry {None, Horizontal, Vertical, Both}
+
+/// Digs a square of floor of `brush_size` centered on the tile, mirrored across the middle of
+/// the map depending on `symmetry`. Returns the number of walls that were dug
+pub fn paint(map: &mut Map, symmetry: Symmetry, brush_size: i32, x: i32, y: i32) -> usize {
+    let mirror_x = map.width - 1 - x;
+    let mirror_y = map.height - 1 - y;
+    let points = match symmetry {
+        Symmetry::None => vec![(x, y)],
+        Symmetry::Horizontal => vec![(x, y), (mirror_x, y)],
+        Symmetry::Vertical => vec![(x, y), (x, mirror_y)],
+        Symmetry::Both => vec![(x, y), (mirror_x, y), (x, mirror_y), (mirror_x, mirror_y)]
+    };
+
+    let mut nb_dug = 0;
+    let half_brush = brush_size / 2;
+    for (px, py) in points {
+        for by in py - half_brush ..= py - half_brush + brush_size - 1 {
+            for bx in px - half_brush ..= px - half_brush + brush_size - 1 {
+                if bx < 1 || bx > map.width - 2 || by < 1 || by > map.height - 2 { continue }
+                if map.is_wall(bx, by) {
+                    map.set_tile(bx, by, TileType::Floor);
+                    nb_dug += 1;
+                }
+            }
+        }
     }
+    nb_dug
 }
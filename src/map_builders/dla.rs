@@ -0,0 +1,133 @@
+use rltk::{LineAlg, Point, RandomNumberGenerator};
+
+use super::{InitialMapBuilder, BuilderMap, Map, Symmetry, paint, TileType};
+
+const TILES_PER_SNAPSHOT: usize = 40;
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum DlaAlgorithm {
+    /// Diggers wander in from anywhere until they bump into the cave
+    WalkInwards,
+    /// Diggers wander out of the middle until they leave the cave
+    WalkOutwards,
+    /// Diggers head straight for the middle from anywhere
+    CentralAttractor
+}
+
+pub struct DlaBuilder {
+    algorithm: DlaAlgorithm,
+    brush_size: i32,
+    symmetry: Symmetry,
+    floor_percent: f32
+}
+
+impl InitialMapBuilder for DlaBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        self.build(rng, build_data);
+    }
+}
+
+impl DlaBuilder {
+    pub fn new(algorithm: DlaAlgorithm, brush_size: i32, symmetry: Symmetry, floor_percent: f32) -> Box<DlaBuilder> {
+        Box::new(DlaBuilder{algorithm, brush_size, symmetry, floor_percent})
+    }
+
+    pub fn walk_inwards() -> Box<DlaBuilder> {
+        DlaBuilder::new(DlaAlgorithm::WalkInwards, 1, Symmetry::None, 0.25)
+    }
+
+    pub fn walk_outwards() -> Box<DlaBuilder> {
+        DlaBuilder::new(DlaAlgorithm::WalkOutwards, 2, Symmetry::None, 0.25)
+    }
+
+    pub fn central_attractor() -> Box<DlaBuilder> {
+        DlaBuilder::new(DlaAlgorithm::CentralAttractor, 2, Symmetry::None, 0.25)
+    }
+
+    /// Caves mirrored left to right, they look like a bug seen from above
+    pub fn insectoid() -> Box<DlaBuilder> {
+        DlaBuilder::new(DlaAlgorithm::CentralAttractor, 2, Symmetry::Horizontal, 0.25)
+    }
+
+    /// Tunnels grown out of the middle, mirrored top to bottom
+    pub fn reflected_tunnels() -> Box<DlaBuilder> {
+        DlaBuilder::new(DlaAlgorithm::WalkOutwards, 1, Symmetry::Vertical, 0.25)
+    }
+
+    /// Caves mirrored on both axes, like an ink blot test
+    pub fn rorschach() -> Box<DlaBuilder> {
+        DlaBuilder::new(DlaAlgorithm::WalkInwards, 2, Symmetry::Both, 0.25)
+    }
+
+    fn build(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        // Seed the cave with a small cross in the middle of the map
+        let (center_x, center_y) = (build_data.map.width / 2, build_data.map.height / 2);
+        for (x, y) in [(center_x, center_y), (center_x - 1, center_y), (center_x + 1, center_y), (center_x, center_y - 1), (center_x, center_y + 1)] {
+            build_data.map.set_tile(x, y, TileType::Floor);
+        }
+        build_data.take_snapshot();
+
+        let total_tiles = build_data.map.width * build_data.map.height;
+        let desired_floor_tiles = (self.floor_percent * total_tiles as f32) as usize;
+        let mut floor_tile_count = 5;
+        let mut since_snapshot = 0;
+
+        while floor_tile_count < desired_floor_tiles {
+            let map = &mut build_data.map;
+            let (x, y) = match self.algorithm {
+                DlaAlgorithm::WalkInwards => walk_inwards_digger(map, rng),
+                DlaAlgorithm::WalkOutwards => walk_outwards_digger(map, rng),
+                DlaAlgorithm::CentralAttractor => central_attractor_digger(map, rng)
+            };
+            let nb_dug = paint(map, self.symmetry, self.brush_size, x, y);
+            floor_tile_count += nb_dug;
+
+            since_snapshot += nb_dug;
+            if since_snapshot >= TILES_PER_SNAPSHOT {
+                since_snapshot = 0;
+                build_data.take_snapshot();
+            }
+        }
+    }
+}
+
+// Returns the last wall a digger crossed before reaching the floor
+fn walk_inwards_digger(map: &Map, rng: &mut RandomNumberGenerator) -> (i32, i32) {
+    let mut digger = (rng.range(1, map.width - 2), rng.range(1, map.height - 2));
+    let mut previous = digger;
+    while map.is_wall(digger.0, digger.1) {
+        previous = digger;
+        digger = stumble(map, rng, digger);
+    }
+    previous
+}
+
+// Returns the first wall a digger reaches from the middle of the map
+fn walk_outwards_digger(map: &Map, rng: &mut RandomNumberGenerator) -> (i32, i32) {
+    let mut digger = (map.width / 2, map.height / 2);
+    while !map.is_wall(digger.0, digger.1) {
+        digger = stumble(map, rng, digger);
+    }
+    digger
+}
+
+// Returns the last wall on the straight line from a random tile to the middle of the map
+fn central_attractor_digger(map: &Map, rng: &mut RandomNumberGenerator) -> (i32, i32) {
+    let digger = Point::new(rng.range(1, map.width - 2), rng.range(1, map.height - 2));
+    let center = Point::new(map.width / 2, map.height / 2);
+    let mut previous = (digger.x, digger.y);
+    for point in rltk::line2d(LineAlg::Bresenham, digger, center) {
+        if !map.is_wall(point.x, point.y) { break }
+        previous = (point.x, point.y);
+    }
+    previous
+}
+
+fn stumble(map: &Map, rng: &mut RandomNumberGenerator, (x, y): (i32, i32)) -> (i32, i32) {
+    match rng.roll_dice(1, 4) {
+        1 => if x > 2 { (x - 1, y) } else { (x, y) },
+        2 => if x < map.width - 3 { (x + 1, y) } else { (x, y) },
+        3 => if y > 2 { (x, y - 1) } else { (x, y) },
+        _ => if y < map.height - 3 { (x, y + 1) } else { (x, y) }
+    }
+}
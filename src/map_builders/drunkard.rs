@@ -0,0 +1,86 @@
+use rltk::RandomNumberGenerator;
+
+use super::{InitialMapBuilder, BuilderMap, TileType};
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum DrunkSpawnMode {StartingPoint, Random}
+
+#[derive(Copy, Clone)]
+pub struct DrunkardSettings {
+    /// Where each walker starts, the first one always starts in the middle of the map
+    pub spawn_mode: DrunkSpawnMode,
+    /// Number of steps a walker takes before stopping
+    pub lifetime: i32,
+    /// Part of the map to dig out, from 0 to 1
+    pub floor_percent: f32
+}
+
+pub struct DrunkardsWalkBuilder {
+    settings: DrunkardSettings
+}
+
+impl InitialMapBuilder for DrunkardsWalkBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        self.build(rng, build_data);
+    }
+}
+
+impl DrunkardsWalkBuilder {
+    pub fn new(settings: DrunkardSettings) -> Box<DrunkardsWalkBuilder> {
+        Box::new(DrunkardsWalkBuilder{settings})
+    }
+
+    /// A single large cave dug around the player
+    pub fn open_area() -> Box<DrunkardsWalkBuilder> {
+        DrunkardsWalkBuilder::new(DrunkardSettings{spawn_mode: DrunkSpawnMode::StartingPoint, lifetime: 400, floor_percent: 0.5})
+    }
+
+    /// Wide halls dug all over the map
+    pub fn open_halls() -> Box<DrunkardsWalkBuilder> {
+        DrunkardsWalkBuilder::new(DrunkardSettings{spawn_mode: DrunkSpawnMode::Random, lifetime: 400, floor_percent: 0.5})
+    }
+
+    /// Many short walkers leaving narrow twisting passages
+    pub fn winding_passages() -> Box<DrunkardsWalkBuilder> {
+        DrunkardsWalkBuilder::new(DrunkardSettings{spawn_mode: DrunkSpawnMode::Random, lifetime: 100, floor_percent: 0.4})
+    }
+
+    fn build(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        // Walkers start from the middle of the map, so it is always dug out
+        let (center_x, center_y) = (build_data.map.width / 2, build_data.map.height / 2);
+        build_data.map.set_tile(center_x, center_y, TileType::Floor);
+
+        let total_tiles = build_data.map.width * build_data.map.height;
+        let desired_floor_tiles = (self.settings.floor_percent * total_tiles as f32) as usize;
+        let mut floor_tile_count = 1;
+        let mut nb_walkers = 0;
+
+        while floor_tile_count < desired_floor_tiles {
+            let map = &mut build_data.map;
+            let (mut x, mut y) = match (self.settings.spawn_mode, nb_walkers) {
+                (DrunkSpawnMode::StartingPoint, _) | (DrunkSpawnMode::Random, 0) => (center_x, center_y),
+                (DrunkSpawnMode::Random, _) => (rng.range(2, map.width - 2), rng.range(2, map.height - 2))
+            };
+
+            // The walker digs where it stands, then stumbles in a random direction without
+            // ever reaching the border of the map
+            for _ in 0..self.settings.lifetime {
+                let idx = map.xy_idx(x, y);
+                if map.tiles[idx] == TileType::Wall {
+                    map.tiles[idx] = TileType::Floor;
+                    floor_tile_count += 1;
+                }
+
+                match rng.roll_dice(1, 4) {
+                    1 => if x > 2 { x -= 1 },
+                    2 => if x < map.width - 3 { x += 1 },
+                    3 => if y > 2 { y -= 1 },
+                    _ => if y < map.height - 3 { y += 1 }
+                }
+            }
+
+            nb_walkers += 1;
+            build_data.take_snapshot();
+        }
+    }
+}
@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+use std::fs;
+
+use serde::Deserialize;
+
+use crate::map::{MAPWIDTH, MAPHEIGHT, RoomTheme};
+use crate::spawner::SPAWNABLE_NAMES;
+use crate::weighted_table::WeightedTable;
+
+use super::MIN_REACHABLE_TILES;
+use super::room_based::ThemeSpawns;
+
+/// Designers can change the progression of the dungeon in this file without recompiling
+const LAYOUT_FILE: &str = "./resources/dungeon_layout.json";
+/// Copy of the layout file made at compile time, used when the file cannot be read
+const DEFAULT_LAYOUT: &str = include_str!("../../resources/dungeon_layout.json");
+
+/// Levels narrower or shorter than this leave no room for the builders to work with
+const MIN_LEVEL_WIDTH: i32 = 20;
+const MIN_LEVEL_HEIGHT: i32 = 20;
+
+/// The starting builders a level can be made with
+#[derive(Copy, Clone, PartialEq, Deserialize)]
+pub enum StarterKind {
+    Simple,
+    Bsp,
+    CellularAutomata,
+    OpenArea,
+    OpenHalls,
+    WindingPassages,
+    Maze,
+    DlaWalkInwards,
+    DlaWalkOutwards,
+    DlaCentralAttractor,
+    DlaInsectoid,
+    DlaReflectedTunnels,
+    DlaRorschach,
+    Voronoi
+}
+
+/// The builders that can be added on top of the starting one
+#[derive(Copy, Clone, PartialEq, Deserialize)]
+pub enum MetaKind {
+    WaveformCollapse,
+    River,
+    GrassPatches,
+    GoblinDen,
+    OrcCamp,
+    Watchtower,
+    Vaults
+}
+
+#[derive(Clone, Deserialize)]
+pub enum ChainLayout {
+    /// The village above the dungeon
+    Town,
+    /// The abandoned keep, exactly as it was painted
+    Keep,
+    /// A random starter with random terrain and prefabs
+    Random,
+    /// Always the same builders. The starting position, the stairs down and the spawns are
+    /// added after them
+    Fixed {starter: StarterKind, builders: Vec<MetaKind>}
+}
+
+/// How the levels of a range of depths are built
+#[derive(Clone, Deserialize)]
+pub struct LevelLayout {
+    pub name: String,
+    pub min_depth: i32,
+    pub max_depth: i32,
+    #[serde(default = "default_width")]
+    pub width: i32,
+    #[serde(default = "default_height")]
+    pub height: i32,
+    pub chain: ChainLayout,
+    pub spawn_table: String,
+    /// There are no stairs down on the last level of the dungeon
+    #[serde(default)]
+    pub last_level: bool,
+    /// Spawned on the stairs down of a boss floor to guard them
+    #[serde(default)]
+    pub boss: Option<String>
+}
+
+fn default_width() -> i32 { MAPWIDTH }
+fn default_height() -> i32 { MAPHEIGHT }
+
+/// The weight of an entry grows by `per_depth` on each level
+#[derive(Clone, Deserialize)]
+pub struct SpawnEntry {
+    pub name: String,
+    pub weight: i32,
+    #[serde(default)]
+    pub per_depth: i32
+}
+
+/// What the rooms of a theme are filled with, from `min_depth` on
+#[derive(Clone, Deserialize)]
+pub struct RoomThemeLayout {
+    pub theme: RoomTheme,
+    #[serde(default)]
+    pub min_depth: i32,
+    pub weight: i32,
+    pub spawn_table: String,
+    /// Spawns per floor tile of the room
+    pub density: f32
+}
+
+/// Why the layout file could not be used, shown in the main menu until dismissed
+pub struct LayoutError(pub String);
+
+/// Which levels make up the dungeon, and what spawns in them
+#[derive(Clone, Deserialize)]
+pub struct DungeonLayout {
+    pub levels: Vec<LevelLayout>,
+    #[serde(default)]
+    pub room_themes: Vec<RoomThemeLayout>,
+    pub spawn_tables: HashMap<String, Vec<SpawnEntry>>
+}
+
+impl DungeonLayout {
+    /// Reads the layout file, fails when it is missing or invalid
+    pub fn load() -> Result<DungeonLayout, String> {
+        let json = fs::read_to_string(LAYOUT_FILE).map_err(|e| format!("Unable to read {}: {}", LAYOUT_FILE, e))?;
+        DungeonLayout::parse(&json).map_err(|e| format!("Invalid dungeon layout in {}: {}", LAYOUT_FILE, e))
+    }
+
+    /// The layout the game was compiled with, used when the layout file cannot be loaded
+    pub fn builtin() -> DungeonLayout {
+        DungeonLayout::parse(DEFAULT_LAYOUT).expect("The default dungeon layout is invalid")
+    }
+
+    pub fn parse(json: &str) -> Result<DungeonLayout, String> {
+        let layout: DungeonLayout = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        if layout.levels.is_empty() {
+            return Err("There are no levels".to_string());
+        }
+        for level in layout.levels.iter() {
+            if level.min_depth > level.max_depth {
+                return Err(format!("Level '{}' ends before it starts", level.name));
+            }
+            if level.width < MIN_LEVEL_WIDTH || level.height < MIN_LEVEL_HEIGHT {
+                return Err(format!("Level '{}' is smaller than {}x{}", level.name, MIN_LEVEL_WIDTH, MIN_LEVEL_HEIGHT));
+            }
+            // Most builders dig out less than half of the map inside its border
+            let inner_tiles = ((level.width - 2) * (level.height - 2)) as usize;
+            if inner_tiles < MIN_REACHABLE_TILES * 2 {
+                return Err(format!("Level '{}' is too small to have {} reachable tiles", level.name, MIN_REACHABLE_TILES));
+            }
+            if !layout.spawn_tables.contains_key(&level.spawn_table) {
+                return Err(format!("Level '{}' uses the unknown spawn table '{}'", level.name, level.spawn_table));
+            }
+            if let Some(boss) = level.boss.as_ref().filter(|boss| !SPAWNABLE_NAMES.contains(&boss.as_str())) {
+                return Err(format!("Level '{}' has the unknown boss '{}'", level.name, boss));
+            }
+        }
+        for (table_name, entries) in layout.spawn_tables.iter() {
+            if let Some(entry) = entries.iter().find(|entry| !SPAWNABLE_NAMES.contains(&entry.name.as_str())) {
+                return Err(format!("Spawn table '{}' has the unknown entry '{}'", table_name, entry.name));
+            }
+        }
+        for room_theme in layout.room_themes.iter() {
+            if !layout.spawn_tables.contains_key(&room_theme.spawn_table) {
+                return Err(format!("Room theme '{}' uses the unknown spawn table '{}'", room_theme.theme.name(), room_theme.spawn_table));
+            }
+        }
+        Ok(layout)
+    }
+
+    /// The first level whose range contains the depth, levels deeper than every range are
+    /// built like the last one
+    pub fn level(&self, depth: i32) -> &LevelLayout {
+        self.levels.iter()
+            .find(|level| level.min_depth <= depth && depth <= level.max_depth)
+            .unwrap_or_else(|| self.levels.last().unwrap())
+    }
+
+    /// The room themes that can be found at this depth
+    pub fn room_themes(&self, depth: i32) -> Vec<ThemeSpawns> {
+        self.room_themes.iter()
+            .filter(|room_theme| room_theme.min_depth <= depth && room_theme.weight > 0)
+            .map(|room_theme| ThemeSpawns{
+                theme: room_theme.theme,
+                weight: room_theme.weight,
+                spawn_table: self.spawn_table(&room_theme.spawn_table, depth),
+                density: room_theme.density
+            })
+            .collect()
+    }
+
+    pub fn spawn_table(&self, name: &str, depth: i32) -> WeightedTable {
+        self.spawn_tables[name].iter().fold(WeightedTable::new(), |table, entry| {
+            table.add(&entry.name, entry.weight + entry.per_depth * depth)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout_json(level: &str, entries: &str) -> String {
+        format!(r#"{{"levels": [{}], "spawn_tables": {{"Table": [{}]}}}}"#, level, entries)
+    }
+
+    const LEVEL: &str = r#"{"name": "Level", "min_depth": 0, "max_depth": 5, "chain": "Random", "spawn_table": "Table"}"#;
+
+    #[test]
+    fn default_layout_is_valid() {
+        assert!(DungeonLayout::parse(DEFAULT_LAYOUT).is_ok());
+    }
+
+    #[test]
+    fn valid_layout_is_accepted() {
+        let layout = DungeonLayout::parse(&layout_json(LEVEL, r#"{"name": "Goblin", "weight": 3}"#)).unwrap();
+        assert_eq!(layout.level(2).width, MAPWIDTH);
+        assert_eq!(layout.level(2).height, MAPHEIGHT);
+    }
+
+    #[test]
+    fn unknown_spawn_entry_is_rejected() {
+        let error = DungeonLayout::parse(&layout_json(LEVEL, r#"{"name": "Gobelin", "weight": 3}"#)).err().unwrap();
+        assert!(error.contains("Gobelin"), "{}", error);
+    }
+
+    #[test]
+    fn unknown_spawn_table_is_rejected() {
+        let level = LEVEL.replace("\"Table\"", "\"Other\"");
+        assert!(DungeonLayout::parse(&layout_json(&level, "")).is_err());
+    }
+
+    #[test]
+    fn too_narrow_level_is_rejected() {
+        let level = LEVEL.replace("\"chain\"", "\"width\": 10, \"height\": 200, \"chain\"");
+        let error = DungeonLayout::parse(&layout_json(&level, "")).err().unwrap();
+        assert!(error.contains("smaller than"), "{}", error);
+    }
+
+    #[test]
+    fn level_without_room_for_enough_reachable_tiles_is_rejected() {
+        let level = LEVEL.replace("\"chain\"", "\"width\": 30, \"height\": 20, \"chain\"");
+        let error = DungeonLayout::parse(&layout_json(&level, "")).err().unwrap();
+        assert!(error.contains("reachable tiles"), "{}", error);
+    }
+
+    #[test]
+    fn default_layout_has_a_boss_floor() {
+        let layout = DungeonLayout::parse(DEFAULT_LAYOUT).unwrap();
+        let boss_floor = layout.levels.iter().find(|level| level.boss.is_some()).expect("There is no boss floor");
+        assert!(!boss_floor.last_level);
+        assert_eq!(layout.level(boss_floor.min_depth).boss.as_deref(), Some("Orc Warlord"));
+    }
+
+    #[test]
+    fn unknown_boss_is_rejected() {
+        let level = LEVEL.replace("\"chain\"", "\"boss\": \"Dragon\", \"chain\"");
+        let error = DungeonLayout::parse(&layout_json(&level, "")).err().unwrap();
+        assert!(error.contains("Dragon"), "{}", error);
+    }
+
+    #[test]
+    fn deeper_levels_are_built_like_the_last_one() {
+        let layout = DungeonLayout::parse(DEFAULT_LAYOUT).unwrap();
+        let last = layout.levels.last().unwrap();
+        assert_eq!(layout.level(last.max_depth + 10).name, last.name);
+    }
+}
@@ -0,0 +1,62 @@
+use rltk::RandomNumberGenerator;
+
+use super::{InitialMapBuilder, BuilderMap, TileType};
+
+const CELLS_PER_SNAPSHOT: usize = 20;
+
+pub struct MazeBuilder {}
+
+impl InitialMapBuilder for MazeBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        self.build(rng, build_data);
+    }
+}
+
+impl MazeBuilder {
+    pub fn new() -> Box<MazeBuilder> {
+        Box::new(MazeBuilder{})
+    }
+
+    fn build(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        // Maze cells are every other tile inside the border, the tiles in between are the walls
+        // that get knocked down between two connected cells
+        let grid_width = (build_data.map.width - 2) / 2;
+        let grid_height = (build_data.map.height - 2) / 2;
+        let cell_tile = |cx: i32, cy: i32| (cx * 2 + 1, cy * 2 + 1);
+
+        let mut visited = vec![false; (grid_width * grid_height) as usize];
+        let mut stack: Vec<(i32, i32)> = vec![(0, 0)];
+        visited[0] = true;
+        let (x, y) = cell_tile(0, 0);
+        build_data.map.set_tile(x, y, TileType::Floor);
+        let mut nb_carved = 1;
+
+        // Recursive backtracking: dig towards a random unvisited neighbour, and step back
+        // along the path when there is none left
+        while let Some(&(cx, cy)) = stack.last() {
+            let neighbors: Vec<(i32, i32)> = [(0, -1), (1, 0), (0, 1), (-1, 0)].iter()
+                .map(|(dx, dy)| (cx + dx, cy + dy))
+                .filter(|(nx, ny)| *nx >= 0 && *nx < grid_width && *ny >= 0 && *ny < grid_height)
+                .filter(|(nx, ny)| !visited[(ny * grid_width + nx) as usize])
+                .collect();
+
+            if neighbors.is_empty() {
+                stack.pop();
+                continue;
+            }
+
+            let (nx, ny) = neighbors[rng.range(0, neighbors.len() as i32) as usize];
+            visited[(ny * grid_width + nx) as usize] = true;
+            let (x, y) = cell_tile(cx, cy);
+            let (next_x, next_y) = cell_tile(nx, ny);
+            build_data.map.set_tile((x + next_x) / 2, (y + next_y) / 2, TileType::Floor);
+            build_data.map.set_tile(next_x, next_y, TileType::Floor);
+            stack.push((nx, ny));
+
+            nb_carved += 1;
+            if nb_carved % CELLS_PER_SNAPSHOT == 0 {
+                build_data.take_snapshot();
+            }
+        }
+    }
+}
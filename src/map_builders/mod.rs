@@ -1,15 +1,53 @@
 mod simple_map;
 use self::simple_map::SimpleMapBuilder;
+mod bsp_dungeon;
+use self::bsp_dungeon::BspDungeonBuilder;
+mod cellular_automata;
+use self::cellular_automata::CellularAutomataBuilder;
+mod drunkard;
+use self::drunkard::DrunkardsWalkBuilder;
+mod maze;
+use self::maze::MazeBuilder;
+mod dla;
+use self::dla::DlaBuilder;
+mod voronoi;
+use self::voronoi::VoronoiBuilder;
+mod town;
+use self::town::TownBuilder;
+mod waveform_collapse;
+use self::waveform_collapse::WaveformCollapseBuilder;
+mod prefabs;
+mod prefab_builder;
+use self::prefab_builder::PrefabBuilder;
+mod room_based;
+use self::room_based::{RoomBasedDoors, RoomBasedSpawner, RoomBasedStartingPosition, RoomThemes, ThemeSpawns};
+mod terrain;
+use self::terrain::{GrassPatches, River};
+mod area_based;
+use self::area_based::{AreaStartingPosition, CullUnreachable, DistantExit, VoronoiSpawning, XStart, YStart};
+mod layout;
+pub use self::layout::{DungeonLayout, LayoutError};
+use self::layout::{ChainLayout, MetaKind, StarterKind};
 
 mod common;
 use common::*;
 use hecs::World;
-use resources::Resources;
+use rltk::{DijkstraMap, RandomNumberGenerator};
 
+use crate::{SHOW_MAPGEN_ANIMATION, spawner};
 use crate::rect::Rect;
-use crate::map::{Map, TileType};
+use crate::map::{Map, TileType, MAPWIDTH, MAPHEIGHT};
 use crate::components::Position;
+use crate::weighted_table::WeightedTable;
 
+/// Levels where the player can reach fewer tiles than this are thrown away
+pub const MIN_REACHABLE_TILES: usize = 300;
+/// Levels made by the chain of a depth are thrown away at most this many times, before falling
+/// back to a plain rooms and corridors level
+const MAX_BUILD_ATTEMPTS: usize = 50;
+/// The generation history is thinned out past this many snapshots, so that it fits in memory
+/// for large levels
+const MAX_SNAPSHOTS: usize = 500;
 
 pub struct MapGenData {
     pub history: Vec<Map>,
@@ -17,15 +55,359 @@ pub struct MapGenData {
     pub timer: f32
 }
 
-pub trait MapBuilder {
-    fn build_map(&mut self);
-    fn spawn_entities(&mut self, world: &mut World, res: &mut Resources);
-    fn get_map(&mut self) -> Map;
-    fn get_starting_position(&mut self) -> Position;
-    fn get_map_history(&self) -> Vec<Map>;
-    fn take_snapshot(&mut self);
+/// Everything the builders of a chain know about the level being generated
+pub struct BuilderMap {
+    pub map: Map,
+    pub starting_position: Option<Position>,
+    pub rooms: Option<Vec<Rect>>,
+    pub spawn_list: Vec<((i32, i32), String)>,
+    /// What the spawners of the chain pick monsters and items from
+    pub spawn_table: WeightedTable,
+    pub history: Vec<Map>,
+    /// Only one snapshot in `snapshot_stride` is kept, it doubles each time the history is full
+    snapshot_stride: usize,
+    snapshots_skipped: usize
+}
+
+impl BuilderMap {
+    fn take_snapshot(&mut self) {
+        if SHOW_MAPGEN_ANIMATION {
+            self.snapshots_skipped += 1;
+            if self.snapshots_skipped < self.snapshot_stride { return }
+            self.snapshots_skipped = 0;
+
+            // Every other snapshot is dropped, the animation still shows the whole generation
+            if self.history.len() >= MAX_SNAPSHOTS {
+                let mut i = 0;
+                self.history.retain(|_snapshot| {
+                    i += 1;
+                    i % 2 == 1
+                });
+                self.snapshot_stride *= 2;
+            }
+
+            self.push_snapshot();
+        }
+    }
+
+    /// Records the finished level whatever the stride, so the animation always ends on it
+    fn take_final_snapshot(&mut self) {
+        if SHOW_MAPGEN_ANIMATION {
+            self.push_snapshot();
+        }
+    }
+
+    fn push_snapshot(&mut self) {
+        // Snapshots are only drawn, they do not need the entities of the tiles
+        let mut snapshot = self.map.clone();
+        snapshot.tile_content = Vec::new();
+        for revealed in snapshot.revealed_tiles.iter_mut() {
+            *revealed = true;
+        }
+        self.history.push(snapshot);
+    }
 }
 
-pub fn random_builder(new_depth: i32) -> Box<dyn MapBuilder> {
-    Box::new(SimpleMapBuilder::new(new_depth))
+/// Makes the first version of a level
+pub trait InitialMapBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap);
+}
+
+/// Changes a level made by the builders before it in the chain
+pub trait MetaMapBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap);
+}
+
+pub struct BuilderChain {
+    starter: Option<Box<dyn InitialMapBuilder>>,
+    builders: Vec<Box<dyn MetaMapBuilder>>,
+    pub build_data: BuilderMap
+}
+
+impl BuilderChain {
+    pub fn new(new_depth: i32, width: i32, height: i32, spawn_table: WeightedTable) -> BuilderChain {
+        BuilderChain{
+            starter: None,
+            builders: Vec::new(),
+            build_data: BuilderMap{
+                map: Map::new(new_depth, width, height),
+                starting_position: None,
+                rooms: None,
+                spawn_list: Vec::new(),
+                spawn_table,
+                history: Vec::new(),
+                snapshot_stride: 1,
+                snapshots_skipped: 0
+            }
+        }
+    }
+
+    pub fn start_with(&mut self, starter: Box<dyn InitialMapBuilder>) {
+        match self.starter {
+            None => self.starter = Some(starter),
+            Some(_) => panic!("A builder chain can only have one starting builder")
+        }
+    }
+
+    pub fn with(&mut self, metabuilder: Box<dyn MetaMapBuilder>) {
+        self.builders.push(metabuilder);
+    }
+
+    /// Runs the chain, and fails when the level it made is not worth playing
+    pub fn build_map(&mut self, rng: &mut RandomNumberGenerator) -> Result<(), String> {
+        match &mut self.starter {
+            None => panic!("Cannot run a builder chain without a starting builder"),
+            Some(starter) => starter.build_map(rng, &mut self.build_data)
+        }
+
+        for metabuilder in self.builders.iter_mut() {
+            metabuilder.build_map(rng, &mut self.build_data);
+        }
+
+        self.build_data.take_final_snapshot();
+        self.validate()
+    }
+
+    fn validate(&mut self) -> Result<(), String> {
+        let start = self.build_data.starting_position.ok_or("The level has no starting position")?;
+        let map = &mut self.build_data.map;
+        let start_idx = map.xy_idx(start.x, start.y);
+        map.set_blocked();
+        let dijkstra_map = DijkstraMap::new(map.width as usize, map.height as usize, &[start_idx], &*map, map.tiles.len() as f32);
+
+        let reachable: Vec<usize> = (0..map.tiles.len()).filter(|idx| dijkstra_map.map[*idx] < f32::MAX).collect();
+        if !reachable.iter().any(|idx| map.tiles[*idx] == TileType::StairsDown) {
+            return Err("The stairs down cannot be reached".to_string());
+        }
+        if reachable.len() < MIN_REACHABLE_TILES {
+            return Err(format!("Only {} tiles can be reached", reachable.len()));
+        }
+        Ok(())
+    }
+
+    pub fn spawn_entities(&self, world: &mut World) {
+        for (point, name) in self.build_data.spawn_list.iter() {
+            spawner::spawn_entity(world, *point, name);
+        }
+    }
+}
+
+// Returns the starting builder and whether it makes rooms
+fn initial_builder(kind: StarterKind) -> (Box<dyn InitialMapBuilder>, bool) {
+    match kind {
+        StarterKind::Simple => (SimpleMapBuilder::new(), true),
+        StarterKind::Bsp => (BspDungeonBuilder::new(), true),
+        StarterKind::CellularAutomata => (CellularAutomataBuilder::new(), false),
+        StarterKind::OpenArea => (DrunkardsWalkBuilder::open_area(), false),
+        StarterKind::OpenHalls => (DrunkardsWalkBuilder::open_halls(), false),
+        StarterKind::WindingPassages => (DrunkardsWalkBuilder::winding_passages(), false),
+        StarterKind::Maze => (MazeBuilder::new(), false),
+        StarterKind::DlaWalkInwards => (DlaBuilder::walk_inwards(), false),
+        StarterKind::DlaWalkOutwards => (DlaBuilder::walk_outwards(), false),
+        StarterKind::DlaCentralAttractor => (DlaBuilder::central_attractor(), false),
+        StarterKind::DlaInsectoid => (DlaBuilder::insectoid(), false),
+        StarterKind::DlaReflectedTunnels => (DlaBuilder::reflected_tunnels(), false),
+        StarterKind::DlaRorschach => (DlaBuilder::rorschach(), false),
+        StarterKind::Voronoi => (VoronoiBuilder::new(), false)
+    }
+}
+
+fn random_initial_builder(rng: &mut RandomNumberGenerator) -> StarterKind {
+    // Mazes are rare, one level in twenty
+    if rng.roll_dice(1, 20) == 1 {
+        return StarterKind::Maze;
+    }
+
+    match rng.roll_dice(1, 13) {
+        1 => StarterKind::Simple,
+        2 => StarterKind::Bsp,
+        3 => StarterKind::CellularAutomata,
+        4 => StarterKind::OpenArea,
+        5 => StarterKind::OpenHalls,
+        6 => StarterKind::WindingPassages,
+        7 => StarterKind::DlaWalkInwards,
+        8 => StarterKind::DlaWalkOutwards,
+        9 => StarterKind::DlaCentralAttractor,
+        10 => StarterKind::DlaInsectoid,
+        11 => StarterKind::DlaReflectedTunnels,
+        12 => StarterKind::DlaRorschach,
+        _ => StarterKind::Voronoi
+    }
+}
+
+fn meta_builder(kind: MetaKind) -> Box<dyn MetaMapBuilder> {
+    match kind {
+        MetaKind::WaveformCollapse => WaveformCollapseBuilder::new(),
+        MetaKind::River => River::new(),
+        MetaKind::GrassPatches => GrassPatches::new(),
+        MetaKind::GoblinDen => PrefabBuilder::sectional(prefabs::GOBLIN_DEN),
+        MetaKind::OrcCamp => PrefabBuilder::sectional(prefabs::ORC_CAMP),
+        MetaKind::Watchtower => PrefabBuilder::sectional(prefabs::WATCHTOWER),
+        MetaKind::Vaults => PrefabBuilder::vaults()
+    }
+}
+
+// Terrain, then a camp or a few vaults on top, for some levels
+fn random_meta_builders(rng: &mut RandomNumberGenerator, new_depth: i32) -> Vec<MetaKind> {
+    let mut builders = Vec::new();
+
+    // Some levels are crossed by a river or overgrown
+    match rng.roll_dice(1, 6) {
+        1 => builders.push(MetaKind::River),
+        2 | 3 => builders.push(MetaKind::GrassPatches),
+        _ => {}
+    }
+
+    // Some levels get a camp or a few vaults on top
+    match rng.roll_dice(1, 6) {
+        1 => {
+            let section = match rng.roll_dice(1, 3) {
+                1 if new_depth >= 3 => MetaKind::OrcCamp,
+                1 | 2 => MetaKind::GoblinDen,
+                _ => MetaKind::Watchtower
+            };
+            builders.push(section);
+        }
+        2 | 3 => builders.push(MetaKind::Vaults),
+        _ => {}
+    }
+
+    builders
+}
+
+fn random_start_position(rng: &mut RandomNumberGenerator) -> (XStart, YStart) {
+    let x = match rng.roll_dice(1, 3) {
+        1 => XStart::Left,
+        2 => XStart::Center,
+        _ => XStart::Right
+    };
+    let y = match rng.roll_dice(1, 3) {
+        1 => YStart::Top,
+        2 => YStart::Center,
+        _ => YStart::Bottom
+    };
+    (x, y)
+}
+
+/// Makes the builder chain of a level as the dungeon layout describes it for this depth
+pub fn level_builder(layout: &DungeonLayout, new_depth: i32, rng: &mut RandomNumberGenerator) -> BuilderChain {
+    let level = layout.level(new_depth);
+    let spawn_table = layout.spawn_table(&level.spawn_table, new_depth);
+    let mut builder = BuilderChain::new(new_depth, level.width, level.height, spawn_table);
+
+    let (starter, weave, builders) = match &level.chain {
+        ChainLayout::Town => {
+            builder.start_with(TownBuilder::new());
+            return builder;
+        }
+        ChainLayout::Keep => {
+            builder.start_with(PrefabBuilder::rex_level(prefabs::KEEP_LEVEL));
+            return builder;
+        }
+        ChainLayout::Fixed{starter, builders} => {
+            let weave = builders.contains(&MetaKind::WaveformCollapse);
+            let builders = builders.iter().copied().filter(|kind| *kind != MetaKind::WaveformCollapse).collect();
+            (*starter, weave, builders)
+        }
+        ChainLayout::Random => {
+            // The abandoned keep is a whole level painted in advance, it is as rare as mazes
+            if rng.roll_dice(1, 20) == 1 {
+                builder.start_with(PrefabBuilder::rex_level(prefabs::KEEP_LEVEL));
+                // Half of the time it is only the sample of a new level woven from its pieces
+                if rng.roll_dice(1, 2) == 1 {
+                    return builder;
+                }
+                builder.with(WaveformCollapseBuilder::new());
+                add_common_builders(&mut builder, false, random_meta_builders(rng, new_depth), Vec::new(), rng);
+                return builder;
+            }
+            let starter = random_initial_builder(rng);
+            // Some levels are woven from the pieces of the level the starter made
+            let weave = rng.roll_dice(1, 8) == 1;
+            (starter, weave, random_meta_builders(rng, new_depth))
+        }
+    };
+
+    let (starter, starter_has_rooms) = initial_builder(starter);
+    builder.start_with(starter);
+    if weave {
+        builder.with(WaveformCollapseBuilder::new());
+    }
+    add_common_builders(&mut builder, starter_has_rooms && !weave, builders, layout.room_themes(new_depth), rng);
+    builder
+}
+
+/// Builds the level of a depth. Levels that are not worth playing are thrown away, the next try
+/// goes on with the same random stream
+pub fn build_level(layout: &DungeonLayout, new_depth: i32, rng: &mut RandomNumberGenerator) -> BuilderChain {
+    for _attempt in 0..MAX_BUILD_ATTEMPTS {
+        let mut builder = level_builder(layout, new_depth, rng);
+        if builder.build_map(rng).is_ok() {
+            return builder;
+        }
+    }
+
+    // The chain of this depth may never make a playable level, rooms and corridors of the
+    // default size always do
+    for _attempt in 0..MAX_BUILD_ATTEMPTS {
+        let mut builder = fallback_builder(layout, new_depth, rng);
+        if builder.build_map(rng).is_ok() {
+            return builder;
+        }
+    }
+    panic!("No playable level could be generated for depth {}", new_depth);
+}
+
+fn fallback_builder(layout: &DungeonLayout, new_depth: i32, rng: &mut RandomNumberGenerator) -> BuilderChain {
+    let spawn_table = layout.spawn_table(&layout.level(new_depth).spawn_table, new_depth);
+    let mut builder = BuilderChain::new(new_depth, MAPWIDTH, MAPHEIGHT, spawn_table);
+    builder.start_with(SimpleMapBuilder::new());
+    add_common_builders(&mut builder, true, Vec::new(), layout.room_themes(new_depth), rng);
+    builder
+}
+
+// Every level made from a starter needs a starting position, stairs down and spawns around
+// the builders it was given
+fn add_common_builders(builder: &mut BuilderChain, has_rooms: bool, builders: Vec<MetaKind>, themes: Vec<ThemeSpawns>, rng: &mut RandomNumberGenerator) {
+    if has_rooms {
+        builder.with(RoomBasedStartingPosition::new());
+    } else {
+        let (x, y) = random_start_position(rng);
+        builder.with(AreaStartingPosition::new(x, y));
+    }
+
+    for kind in builders {
+        builder.with(meta_builder(kind));
+    }
+
+    builder.with(CullUnreachable::new());
+    builder.with(DistantExit::new());
+
+    if has_rooms {
+        builder.with(RoomBasedDoors::new());
+        builder.with(RoomThemes::new(themes));
+        builder.with(RoomBasedSpawner::new());
+    } else {
+        builder.with(VoronoiSpawning::new());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_ends_on_the_finished_level() {
+        let mut chain = BuilderChain::new(1, MAPWIDTH, MAPHEIGHT, WeightedTable::new());
+        // As if the history had been thinned out so often that no builder snapshot is kept
+        chain.build_data.snapshot_stride = usize::MAX;
+        chain.start_with(SimpleMapBuilder::new());
+        chain.with(RoomBasedStartingPosition::new());
+        chain.with(DistantExit::new());
+        let mut rng = RandomNumberGenerator::seeded(3);
+        assert!(chain.build_map(&mut rng).is_ok());
+
+        let last = chain.build_data.history.last().expect("The history is empty");
+        assert!(last.tiles == chain.build_data.map.tiles);
+    }
 }
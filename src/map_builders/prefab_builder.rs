@@ -0,0 +1,232 @@
+use std::collections::HashSet;
+
+use rltk::{DijkstraMap, RandomNumberGenerator};
+use rltk::rex::XpFile;
+
+use super::{InitialMapBuilder, MetaMapBuilder, BuilderMap, TileType, Position};
+use super::prefabs::{GLYPH_SPAWNS, HorizontalPlacement, VerticalPlacement, PrefabSection, VAULTS};
+
+const MAX_VAULTS: i32 = 3;
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum PrefabMode {
+    /// A whole level painted in REX Paint
+    RexLevel{template: &'static [u8]},
+    /// A template stamped over a part of the level
+    Sectional{section: PrefabSection},
+    /// Small templates stamped in open areas of the level
+    RoomVaults
+}
+
+pub struct PrefabBuilder {
+    mode: PrefabMode
+}
+
+impl InitialMapBuilder for PrefabBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        self.build(rng, build_data);
+    }
+}
+
+impl MetaMapBuilder for PrefabBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        self.build(rng, build_data);
+    }
+}
+
+impl PrefabBuilder {
+    pub fn new(mode: PrefabMode) -> Box<PrefabBuilder> {
+        Box::new(PrefabBuilder{mode})
+    }
+
+    pub fn rex_level(template: &'static [u8]) -> Box<PrefabBuilder> {
+        PrefabBuilder::new(PrefabMode::RexLevel{template})
+    }
+
+    pub fn sectional(section: PrefabSection) -> Box<PrefabBuilder> {
+        PrefabBuilder::new(PrefabMode::Sectional{section})
+    }
+
+    pub fn vaults() -> Box<PrefabBuilder> {
+        PrefabBuilder::new(PrefabMode::RoomVaults)
+    }
+
+    fn build(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        match self.mode {
+            PrefabMode::RexLevel{template} => load_rex_level(build_data, template),
+            PrefabMode::Sectional{section} => apply_section(build_data, section),
+            PrefabMode::RoomVaults => apply_room_vaults(rng, build_data)
+        }
+        build_data.take_snapshot();
+    }
+}
+
+fn load_rex_level(build_data: &mut BuilderMap, template: &'static [u8]) {
+    let xp = XpFile::read(&mut &template[..]).expect("Unable to read a REX Paint template");
+
+    // Layers are painted over each other, cells with a transparent background let the
+    // layers below show through
+    for layer in xp.layers.iter() {
+        for y in 0..i32::min(layer.height as i32, build_data.map.height) {
+            for x in 0..i32::min(layer.width as i32, build_data.map.width) {
+                let cell = layer.get(x as usize, y as usize).unwrap();
+                if cell.bg.is_transparent() { continue }
+                char_to_map(build_data, rltk::to_char(cell.ch as u8), (x, y));
+            }
+        }
+    }
+}
+
+fn apply_section(build_data: &mut BuilderMap, section: PrefabSection) {
+    let reachable = reachable_tiles(build_data);
+
+    let (width, height) = (section.width as i32, section.height as i32);
+    let x = match section.placement.0 {
+        HorizontalPlacement::Left => 1,
+        HorizontalPlacement::Center => (build_data.map.width - width) / 2,
+        HorizontalPlacement::Right => build_data.map.width - width - 1
+    };
+    let y = match section.placement.1 {
+        VerticalPlacement::Top => 1,
+        VerticalPlacement::Center => (build_data.map.height - height) / 2,
+        VerticalPlacement::Bottom => build_data.map.height - height - 1
+    };
+    let stamped = stamp(build_data, section.template, section.width, section.height, x, y);
+
+    // Move the start out of the section to the closest tile it could reach before
+    if let Some(start) = build_data.starting_position {
+        if stamped.contains(&(start.x, start.y)) {
+            let map = &build_data.map;
+            let new_start = (0..map.tiles.len())
+                .filter(|idx| reachable[*idx])
+                .map(|idx| map.idx_xy(idx))
+                .filter(|point| !stamped.contains(point))
+                .min_by_key(|(x, y)| (x - start.x) * (x - start.x) + (y - start.y) * (y - start.y));
+            if let Some((x, y)) = new_start {
+                build_data.starting_position = Some(Position{x, y});
+            }
+        }
+    }
+}
+
+fn apply_room_vaults(rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+    let reachable = reachable_tiles(build_data);
+    let start = build_data.starting_position.map(|start| (start.x, start.y));
+    let depth = build_data.map.depth;
+
+    let mut vaults: Vec<_> = VAULTS.iter()
+        .filter(|vault| depth >= vault.first_depth && depth <= vault.last_depth)
+        .collect();
+    let nb_vaults = i32::min(rng.roll_dice(1, MAX_VAULTS), vaults.len() as i32);
+    let mut used: HashSet<(i32, i32)> = HashSet::new();
+
+    for _ in 0..nb_vaults {
+        let vault = vaults.remove(rng.range(0, vaults.len() as i32) as usize);
+        let (width, height) = (vault.width as i32, vault.height as i32);
+
+        // A vault only goes where it fits on reachable floor, away from the start and the
+        // other vaults
+        let map = &build_data.map;
+        let mut candidates: Vec<(i32, i32)> = Vec::new();
+        for y in 1..map.height - height {
+            for x in 1..map.width - width {
+                let fits = (0..height).all(|vy| (0..width).all(|vx| {
+                    let point = (x + vx, y + vy);
+                    let idx = map.xy_idx(point.0, point.1);
+                    reachable[idx] && map.tiles[idx] == TileType::Floor && Some(point) != start && !used.contains(&point)
+                }));
+                if fits { candidates.push((x, y)); }
+            }
+        }
+        if candidates.is_empty() { continue }
+
+        let (x, y) = candidates[rng.range(0, candidates.len() as i32) as usize];
+        used.extend(stamp(build_data, vault.template, vault.width, vault.height, x, y));
+        build_data.take_snapshot();
+    }
+}
+
+// Every tile counts as reachable until the chain knows where the player starts
+fn reachable_tiles(build_data: &mut BuilderMap) -> Vec<bool> {
+    let map = &mut build_data.map;
+    match build_data.starting_position {
+        None => vec![true; map.tiles.len()],
+        Some(start) => {
+            let start_idx = map.xy_idx(start.x, start.y);
+            map.set_blocked();
+            let dijkstra_map = DijkstraMap::new(map.width as usize, map.height as usize, &[start_idx], &*map, map.tiles.len() as f32);
+            dijkstra_map.map.iter().map(|distance| *distance < f32::MAX).collect()
+        }
+    }
+}
+
+// Returns the tiles painted by the template, lines shorter than the template are padded with spaces
+fn stamp(build_data: &mut BuilderMap, template: &str, width: usize, height: usize, x: i32, y: i32) -> HashSet<(i32, i32)> {
+    let mut stamped = HashSet::new();
+    for (ty, line) in template.trim_start_matches('\n').lines().take(height).enumerate() {
+        for (tx, glyph) in line.chars().take(width).enumerate() {
+            let point = (x + tx as i32, y + ty as i32);
+            if char_to_map(build_data, glyph, point) {
+                stamped.insert(point);
+            }
+        }
+    }
+    stamped
+}
+
+// Returns false when the glyph leaves the tile untouched
+fn char_to_map(build_data: &mut BuilderMap, glyph: char, (x, y): (i32, i32)) -> bool {
+    if glyph == ' ' { return false }
+    build_data.spawn_list.retain(|(point, _name)| *point != (x, y));
+
+    let tile = match glyph {
+        '#' | '█' => TileType::Wall,
+        '>' => TileType::StairsDown,
+        '.' => TileType::Floor,
+        '"' => TileType::Grass,
+        '~' => TileType::ShallowWater,
+        '≈' => TileType::DeepWater,
+        '^' => TileType::Lava,
+        '=' => TileType::Bridge,
+        '@' => {
+            build_data.starting_position = Some(Position{x, y});
+            TileType::Floor
+        }
+        _ => {
+            // The shipped templates are checked by the tests, an unknown glyph is left as a floor
+            let spawn = GLYPH_SPAWNS.iter().find(|(spawn_glyph, _name)| *spawn_glyph == glyph);
+            debug_assert!(spawn.is_some(), "Unknown glyph '{}' in a prefab template", glyph);
+            if let Some((_glyph, name)) = spawn {
+                build_data.spawn_list.push(((x, y), name.to_string()));
+            }
+            TileType::Floor
+        }
+    };
+    build_data.map.set_tile(x, y, tile);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::BuilderChain;
+    use super::super::prefabs::{GOBLIN_DEN, KEEP_LEVEL, ORC_CAMP, WATCHTOWER};
+    use crate::map::{MAPWIDTH, MAPHEIGHT};
+    use crate::weighted_table::WeightedTable;
+
+    fn empty_level() -> BuilderMap {
+        BuilderChain::new(1, MAPWIDTH, MAPHEIGHT, WeightedTable::new()).build_data
+    }
+
+    // Unknown glyphs fail the debug assertion of char_to_map
+    #[test]
+    fn shipped_templates_only_use_known_glyphs() {
+        for section in [ORC_CAMP, GOBLIN_DEN, WATCHTOWER].iter() {
+            stamp(&mut empty_level(), section.template, section.width, section.height, 1, 1);
+        }
+        for vault in VAULTS.iter() {
+            stamp(&mut empty_level(), vault.template, vault.width, vault.height, 1, 1);
+        }
+        load_rex_level(&mut empty_level(), KEEP_LEVEL);
+    }
+}
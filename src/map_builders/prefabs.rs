@@ -0,0 +1,156 @@
+// Templates use one glyph per tile: '#' is a wall, '.' a floor, '@' the start, '>' the stairs,
+// '"' grass, '~' shallow water, '≈' deep water, '^' lava, '=' a bridge, and a space leaves the
+// tile underneath untouched. Other glyphs spawn an entity on a floor, see `GLYPH_SPAWNS`
+
+pub const GLYPH_SPAWNS: &[(char, &str)] = &[
+    ('g', "Goblin"),
+    ('o', "Orc"),
+    ('p', "Health Potion"),
+    ('(', "Magic Missile Scroll"),
+    ('*', "Fireball Scroll"),
+    ('&', "Confusion Scroll"),
+    ('/', "Dagger"),
+    ('|', "Longsword"),
+    ('[', "Shield"),
+    (']', "Tower Shield")
+];
+
+/// An abandoned keep painted in REX Paint
+pub const KEEP_LEVEL: &[u8] = include_bytes!("../../resources/keep.xp");
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum HorizontalPlacement {Left, Center, Right}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum VerticalPlacement {Top, Center, Bottom}
+
+#[derive(PartialEq, Copy, Clone)]
+pub struct PrefabSection {
+    pub template: &'static str,
+    pub width: usize,
+    pub height: usize,
+    pub placement: (HorizontalPlacement, VerticalPlacement)
+}
+
+pub const ORC_CAMP: PrefabSection = PrefabSection{
+    template: ORC_CAMP_TEMPLATE,
+    width: 12,
+    height: 9,
+    placement: (HorizontalPlacement::Right, VerticalPlacement::Center)
+};
+
+const ORC_CAMP_TEMPLATE: &str = r#"
+ ..........
+.##########.
+.#........#.
+.#.o....o.#.
+...o.p..o...
+.#........#.
+.#.o....o.#.
+.##########.
+ ..........
+"#;
+
+pub const GOBLIN_DEN: PrefabSection = PrefabSection{
+    template: GOBLIN_DEN_TEMPLATE,
+    width: 10,
+    height: 7,
+    placement: (HorizontalPlacement::Left, VerticalPlacement::Bottom)
+};
+
+const GOBLIN_DEN_TEMPLATE: &str = r#"
+ ........
+.###..###.
+.#g....g#.
+.#..pp..#.
+.#g....g#.
+.###..###.
+ ........
+"#;
+
+pub const WATCHTOWER: PrefabSection = PrefabSection{
+    template: WATCHTOWER_TEMPLATE,
+    width: 9,
+    height: 7,
+    placement: (HorizontalPlacement::Center, VerticalPlacement::Top)
+};
+
+const WATCHTOWER_TEMPLATE: &str = r#"
+.........
+.##...##.
+.#.....#.
+...g(g...
+.#.....#.
+.##...##.
+.........
+"#;
+
+/// A small template stamped in an open area of a level, between two depths included
+#[derive(PartialEq, Copy, Clone)]
+pub struct PrefabRoom {
+    pub template: &'static str,
+    pub width: usize,
+    pub height: usize,
+    pub first_depth: i32,
+    pub last_depth: i32
+}
+
+pub const VAULTS: &[PrefabRoom] = &[
+    PrefabRoom{template: SHRINE, width: 7, height: 5, first_depth: 1, last_depth: 4},
+    PrefabRoom{template: CHECKERBOARD, width: 5, height: 5, first_depth: 1, last_depth: i32::MAX},
+    PrefabRoom{template: ORC_AMBUSH, width: 7, height: 7, first_depth: 3, last_depth: i32::MAX},
+    PrefabRoom{template: ARMORY, width: 9, height: 6, first_depth: 4, last_depth: i32::MAX},
+    PrefabRoom{template: LAVA_POOL, width: 7, height: 5, first_depth: 3, last_depth: i32::MAX},
+    PrefabRoom{template: FOUNTAIN, width: 7, height: 5, first_depth: 1, last_depth: i32::MAX}
+];
+
+const SHRINE: &str = r#"
+.......
+.#...#.
+...p...
+.#...#.
+.......
+"#;
+
+const CHECKERBOARD: &str = r#"
+.....
+.g#p.
+.#(#.
+.p#g.
+.....
+"#;
+
+const ORC_AMBUSH: &str = r#"
+.......
+.o.#.o.
+..###..
+.#.*.#.
+..###..
+.o.#.o.
+.......
+"#;
+
+const ARMORY: &str = r#"
+.........
+.#######.
+.#/.|.]#.
+.#.....#.
+.###.###.
+.........
+"#;
+
+const LAVA_POOL: &str = r#"
+.......
+.^^^^^.
+.^^*^^.
+.^^^^^.
+.......
+"#;
+
+const FOUNTAIN: &str = r#"
+.......
+..~~~..
+.~~≈~~.
+..~~~p.
+.......
+"#;
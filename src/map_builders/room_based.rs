@@ -0,0 +1,174 @@
+use rltk::RandomNumberGenerator;
+
+use crate::spawner;
+use crate::map::RoomTheme;
+use crate::weighted_table::WeightedTable;
+
+use super::{MetaMapBuilder, BuilderMap, Map, TileType, Position};
+
+/// Starts the player in the middle of the first room
+pub struct RoomBasedStartingPosition {}
+
+impl MetaMapBuilder for RoomBasedStartingPosition {
+    fn build_map(&mut self, _rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let rooms = build_data.rooms.as_ref().expect("Room based starting position needs rooms");
+        let (x, y) = rooms[0].center();
+        build_data.starting_position = Some(Position{x, y});
+    }
+}
+
+impl RoomBasedStartingPosition {
+    pub fn new() -> Box<RoomBasedStartingPosition> {
+        Box::new(RoomBasedStartingPosition{})
+    }
+}
+
+/// Spawns monsters and items on the floor of every room but the first one
+pub struct RoomBasedSpawner {}
+
+impl MetaMapBuilder for RoomBasedSpawner {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let rooms = build_data.rooms.as_ref().expect("Room based spawning needs rooms");
+        for room in rooms.iter().skip(1) {
+            // Themed rooms were already filled
+            if build_data.map.room_theme(room.x1, room.y1).is_some() { continue }
+
+            let mut area: Vec<(i32, i32)> = Vec::new();
+            for y in room.y1 .. room.y2 {
+                for x in room.x1 .. room.x2 {
+                    let idx = build_data.map.xy_idx(x, y);
+                    if build_data.map.tiles[idx].is_open_ground() && !build_data.spawn_list.iter().any(|(point, _name)| *point == (x, y)) {
+                        area.push((x, y));
+                    }
+                }
+            }
+            spawner::spawn_region(rng, &area, build_data.map.depth, &build_data.spawn_table, &mut build_data.spawn_list);
+        }
+    }
+}
+
+impl RoomBasedSpawner {
+    pub fn new() -> Box<RoomBasedSpawner> {
+        Box::new(RoomBasedSpawner{})
+    }
+}
+
+/// What spawns in the rooms of a theme, and how often the theme is picked compared to the others
+pub struct ThemeSpawns {
+    pub theme: RoomTheme,
+    pub weight: i32,
+    pub spawn_table: WeightedTable,
+    /// Spawns per floor tile of the room
+    pub density: f32
+}
+
+/// Gives a theme to some of the rooms, and fills them with its furniture, monsters and items.
+/// The first room, where the player starts, is always left alone
+pub struct RoomThemes {
+    themes: Vec<ThemeSpawns>
+}
+
+impl MetaMapBuilder for RoomThemes {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let total_weight: i32 = self.themes.iter().map(|theme| theme.weight).sum();
+        if total_weight <= 0 { return }
+
+        let rooms = build_data.rooms.as_ref().expect("Room themes need rooms");
+        for room in rooms.iter().skip(1) {
+            // One room in three gets a theme
+            if rng.roll_dice(1, 3) != 1 { continue }
+
+            let mut roll = rng.range(0, total_weight);
+            let theme = self.themes.iter().find(|theme| {
+                roll -= theme.weight;
+                roll < 0
+            }).unwrap();
+            build_data.map.room_themes.push((*room, theme.theme));
+
+            // Furniture lines the walls, the rest of the floor is left to the spawns
+            let mut area: Vec<(i32, i32)> = Vec::new();
+            for y in room.y1 .. room.y2 {
+                for x in room.x1 .. room.x2 {
+                    let idx = build_data.map.xy_idx(x, y);
+                    if !build_data.map.tiles[idx].is_open_ground() || build_data.spawn_list.iter().any(|(point, _name)| *point == (x, y)) { continue }
+
+                    let along_wall = x == room.x1 || x == room.x2 - 1 || y == room.y1 || y == room.y2 - 1;
+                    if along_wall && rng.roll_dice(1, 3) == 1 {
+                        build_data.spawn_list.push(((x, y), decoration(theme.theme).to_string()));
+                    } else {
+                        area.push((x, y));
+                    }
+                }
+            }
+
+            let nb_spawns = i32::max(1, (area.len() as f32 * theme.density).round() as i32);
+            spawner::fill_region(rng, &area, nb_spawns, &theme.spawn_table, &mut build_data.spawn_list);
+        }
+    }
+}
+
+impl RoomThemes {
+    pub fn new(themes: Vec<ThemeSpawns>) -> Box<RoomThemes> {
+        Box::new(RoomThemes{themes})
+    }
+}
+
+fn decoration(theme: RoomTheme) -> &'static str {
+    match theme {
+        RoomTheme::TreasureVault => "Gilded Statue",
+        RoomTheme::MonsterDen => "Bones",
+        RoomTheme::Armory => "Weapon Rack",
+        RoomTheme::Library => "Bookshelf",
+        RoomTheme::Shrine => "Candle"
+    }
+}
+
+/// Puts a door on every corridor tile that enters a room
+pub struct RoomBasedDoors {}
+
+impl MetaMapBuilder for RoomBasedDoors {
+    fn build_map(&mut self, _rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let rooms = build_data.rooms.as_ref().expect("Room based doors need rooms");
+        let mut doors: Vec<(i32, i32)> = Vec::new();
+
+        for room in rooms.iter() {
+            // The tiles around the room, corners excluded, a corridor enters where one of them
+            // is floor between two walls
+            let mut entrances: Vec<(i32, i32)> = Vec::new();
+            for x in room.x1 .. room.x2 {
+                for y in [room.y1 - 1, room.y2] {
+                    if is_between_walls(&build_data.map, x, y, (1, 0)) { entrances.push((x, y)); }
+                }
+            }
+            for y in room.y1 .. room.y2 {
+                for x in [room.x1 - 1, room.x2] {
+                    if is_between_walls(&build_data.map, x, y, (0, 1)) { entrances.push((x, y)); }
+                }
+            }
+
+            for point in entrances {
+                let is_start = build_data.starting_position.is_some_and(|start| (start.x, start.y) == point);
+                let is_used = build_data.spawn_list.iter().any(|(spawn_point, _name)| *spawn_point == point);
+                let next_to_door = doors.iter().any(|(x, y)| (x - point.0).abs() <= 1 && (y - point.1).abs() <= 1);
+                if !is_start && !is_used && !next_to_door {
+                    doors.push(point);
+                }
+            }
+        }
+
+        for point in doors {
+            build_data.spawn_list.push((point, "Door".to_string()));
+        }
+    }
+}
+
+impl RoomBasedDoors {
+    pub fn new() -> Box<RoomBasedDoors> {
+        Box::new(RoomBasedDoors{})
+    }
+}
+
+fn is_between_walls(map: &Map, x: i32, y: i32, (dx, dy): (i32, i32)) -> bool {
+    if x < 1 || x > map.width - 2 || y < 1 || y > map.height - 2 { return false }
+    map.tiles[map.xy_idx(x, y)] == TileType::Floor && map.is_wall(x - dx, y - dy) && map.is_wall(x + dx, y + dy)
+}
@@ -0,0 +1,82 @@
+use rltk::RandomNumberGenerator;
+
+use super::{MetaMapBuilder, BuilderMap, Map, TileType};
+
+const BRIDGE_SPACING: i32 = 8;
+const TILES_PER_GRASS_PATCH: i32 = 300;
+
+/// A river flowing from the top to the bottom of the level. It floods the floor it crosses,
+/// with bridges where the floor goes on on both sides
+pub struct River {}
+
+impl MetaMapBuilder for River {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let start = build_data.starting_position.map(|start| (start.x, start.y));
+        let map = &mut build_data.map;
+
+        let mut x = rng.range(map.width / 4, map.width * 3 / 4);
+        let mut rows_since_bridge = BRIDGE_SPACING;
+        let mut was_crossing = false;
+        for y in 1..map.height - 1 {
+            x = (x + rng.range(-1, 2)).clamp(2, map.width - 4);
+
+            // Two tiles of deep water between shallow banks, the start stays dry
+            let crossing = (x..x + 2).any(|core_x| can_flood(map, core_x, y, start));
+            let bridge = crossing && (!was_crossing || rows_since_bridge >= BRIDGE_SPACING);
+            for river_x in x - 1..x + 3 {
+                if !can_flood(map, river_x, y, start) { continue }
+                let tile = match river_x - x {
+                    -1 | 2 => TileType::ShallowWater,
+                    _ if bridge => TileType::Bridge,
+                    _ => TileType::DeepWater
+                };
+                map.set_tile(river_x, y, tile);
+            }
+
+            was_crossing = crossing;
+            rows_since_bridge = if bridge { 0 } else { rows_since_bridge + 1 };
+        }
+        build_data.take_snapshot();
+    }
+}
+
+impl River {
+    pub fn new() -> Box<River> {
+        Box::new(River{})
+    }
+}
+
+fn can_flood(map: &Map, x: i32, y: i32, start: Option<(i32, i32)>) -> bool {
+    let near_start = start.is_some_and(|(start_x, start_y)| (x - start_x).abs() <= 1 && (y - start_y).abs() <= 1);
+    map.tiles[map.xy_idx(x, y)] == TileType::Floor && !near_start
+}
+
+/// Round patches of grass growing on the floor
+pub struct GrassPatches {}
+
+impl MetaMapBuilder for GrassPatches {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let map = &mut build_data.map;
+        let nb_patches = map.width * map.height / TILES_PER_GRASS_PATCH;
+        for _ in 0..nb_patches {
+            let (center_x, center_y) = (rng.range(1, map.width - 1), rng.range(1, map.height - 1));
+            let radius = rng.range(2, 6);
+            for y in i32::max(1, center_y - radius)..i32::min(map.height - 1, center_y + radius + 1) {
+                for x in i32::max(1, center_x - radius)..i32::min(map.width - 1, center_x + radius + 1) {
+                    let idx = map.xy_idx(x, y);
+                    let in_patch = (x - center_x) * (x - center_x) + (y - center_y) * (y - center_y) <= radius * radius;
+                    if in_patch && map.tiles[idx] == TileType::Floor {
+                        map.tiles[idx] = TileType::Grass;
+                    }
+                }
+            }
+        }
+        build_data.take_snapshot();
+    }
+}
+
+impl GrassPatches {
+    pub fn new() -> Box<GrassPatches> {
+        Box::new(GrassPatches{})
+    }
+}
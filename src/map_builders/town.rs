@@ -0,0 +1,120 @@
+use rltk::RandomNumberGenerator;
+
+use super::{InitialMapBuilder, BuilderMap, Rect, TileType, Position};
+
+const ROAD_WIDTH: i32 = 3;
+/// Columns left free at the east end of the road for the dungeon entrance
+const ENTRANCE_WIDTH: i32 = 12;
+
+/// A village on the surface, along a road leading to the dungeon entrance. The biggest
+/// building is the tavern, the next two are shops and the others are houses
+pub struct TownBuilder {}
+
+impl InitialMapBuilder for TownBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        self.build(rng, build_data);
+    }
+}
+
+impl TownBuilder {
+    pub fn new() -> Box<TownBuilder> {
+        Box::new(TownBuilder{})
+    }
+
+    fn build(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let (width, height) = (build_data.map.width, build_data.map.height);
+        for y in 1..height - 1 {
+            for x in 1..width - 1 {
+                build_data.map.set_tile(x, y, TileType::Grass);
+            }
+        }
+
+        // The road crosses the village from west to east
+        let road_y = height / 2 - ROAD_WIDTH / 2;
+        for y in road_y..road_y + ROAD_WIDTH {
+            for x in 1..width - 1 {
+                build_data.map.set_tile(x, y, TileType::Floor);
+            }
+        }
+        build_data.take_snapshot();
+
+        let mut buildings = self.place_buildings(rng, build_data, road_y);
+        buildings.sort_by_key(|building| -(building.width() * building.height()));
+
+        for (i, building) in buildings.iter().enumerate() {
+            let residents: Vec<&str> = match i {
+                0 => {
+                    let mut residents = vec!["Barkeep"];
+                    residents.extend(vec!["Patron"; rng.range(2, 5) as usize]);
+                    residents
+                }
+                1 => vec!["Blacksmith"],
+                2 => vec!["Alchemist"],
+                _ if rng.roll_dice(1, 2) == 1 => vec!["Townsperson"],
+                _ => Vec::new()
+            };
+            for name in residents {
+                let x = rng.range(building.x1 + 1, building.x2);
+                let y = rng.range(building.y1 + 1, building.y2);
+                if !build_data.spawn_list.iter().any(|(point, _name)| *point == (x, y)) {
+                    build_data.spawn_list.push(((x, y), name.to_string()));
+                }
+            }
+        }
+
+        for _ in 0..rng.range(3, 6) {
+            let point = (rng.range(4, width - ENTRANCE_WIDTH), rng.range(road_y, road_y + ROAD_WIDTH));
+            if !build_data.spawn_list.iter().any(|(spawn_point, _name)| *spawn_point == point) {
+                build_data.spawn_list.push((point, "Townsperson".to_string()));
+            }
+        }
+
+        // The player walks in from the west, the dungeon entrance is at the east end
+        let road_middle = road_y + ROAD_WIDTH / 2;
+        build_data.starting_position = Some(Position{x: 2, y: road_middle});
+        build_data.map.set_tile(width - ENTRANCE_WIDTH / 2, road_middle, TileType::StairsDown);
+        build_data.take_snapshot();
+    }
+
+    // Buildings line both sides of the road, each with a door and a path to the road
+    fn place_buildings(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap, road_y: i32) -> Vec<Rect> {
+        let (width, height) = (build_data.map.width, build_data.map.height);
+        let mut buildings: Vec<Rect> = Vec::new();
+
+        for north_side in [true, false] {
+            let mut x = rng.range(2, 5);
+            loop {
+                let building_width = rng.range(7, 14);
+                if x + building_width >= width - ENTRANCE_WIDTH { break }
+                let space = if north_side { road_y - 3 } else { height - road_y - ROAD_WIDTH - 4 };
+                let building_height = i32::min(rng.range(5, 9), space);
+                let setback = rng.range(1, 3);
+                let y = if north_side { road_y - setback - building_height } else { road_y + ROAD_WIDTH + setback - 1 };
+                if building_height < 4 || y < 1 || y + building_height >= height - 1 { break }
+                let building = Rect::new(x, y, building_width, building_height);
+
+                // Walls around a wooden floor
+                for by in building.y1..=building.y2 {
+                    for bx in building.x1..=building.x2 {
+                        let is_wall = bx == building.x1 || bx == building.x2 || by == building.y1 || by == building.y2;
+                        build_data.map.set_tile(bx, by, if is_wall { TileType::Wall } else { TileType::Floor });
+                    }
+                }
+
+                let door_x = rng.range(building.x1 + 1, building.x2);
+                let (door_y, path) = if north_side { (building.y2, building.y2 + 1..road_y) } else { (building.y1, road_y + ROAD_WIDTH..building.y1) };
+                build_data.map.set_tile(door_x, door_y, TileType::Floor);
+                build_data.spawn_list.push(((door_x, door_y), "Door".to_string()));
+                for path_y in path {
+                    build_data.map.set_tile(door_x, path_y, TileType::Floor);
+                }
+
+                buildings.push(building);
+                build_data.take_snapshot();
+                x += building_width + rng.range(2, 5);
+            }
+        }
+
+        buildings
+    }
+}
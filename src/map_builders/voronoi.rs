@@ -0,0 +1,72 @@
+use std::collections::BTreeMap;
+
+use rltk::RandomNumberGenerator;
+
+use super::{InitialMapBuilder, BuilderMap, TileType};
+
+const NB_SEEDS: usize = 32;
+const OPENINGS_PER_SNAPSHOT: usize = 5;
+
+pub struct VoronoiBuilder {}
+
+impl InitialMapBuilder for VoronoiBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        self.build(rng, build_data);
+    }
+}
+
+impl VoronoiBuilder {
+    pub fn new() -> Box<VoronoiBuilder> {
+        Box::new(VoronoiBuilder{})
+    }
+
+    fn build(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        // Every tile inside the border belongs to the region of its closest seed
+        let seeds: Vec<(i32, i32)> = (0..NB_SEEDS).map(|_| (rng.range(1, build_data.map.width - 1), rng.range(1, build_data.map.height - 1))).collect();
+        let mut region_of: Vec<Option<usize>> = vec![None; build_data.map.tiles.len()];
+        for y in 1..build_data.map.height - 1 {
+            for x in 1..build_data.map.width - 1 {
+                let closest_seed = seeds.iter().enumerate()
+                    .min_by_key(|(_i, (seed_x, seed_y))| (x - seed_x) * (x - seed_x) + (y - seed_y) * (y - seed_y))
+                    .map(|(i, _seed)| i)
+                    .unwrap();
+                region_of[build_data.map.xy_idx(x, y)] = Some(closest_seed);
+            }
+        }
+
+        // Tiles next to another region are walls, the others are the floor of their region.
+        // The border tiles on each side of two neighbouring regions are kept as possible openings
+        let mut openings: BTreeMap<(usize, usize), Vec<(usize, usize)>> = BTreeMap::new();
+        for (idx, region) in region_of.iter().enumerate() {
+            let region = match region { Some(region) => *region, None => continue };
+            let (x, y) = build_data.map.idx_xy(idx);
+            let mut on_border = false;
+            for (dx, dy) in [(1, 0), (0, 1), (-1, 0), (0, -1)] {
+                let other_idx = build_data.map.xy_idx(x + dx, y + dy);
+                match region_of[other_idx] {
+                    Some(other) if other != region => {
+                        on_border = true;
+                        if region < other {
+                            openings.entry((region, other)).or_default().push((idx, other_idx));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            if !on_border {
+                build_data.map.tiles[idx] = TileType::Floor;
+            }
+        }
+        build_data.take_snapshot();
+
+        // Knock a hole through each wall between two neighbouring regions
+        for (i, border) in openings.values().enumerate() {
+            let (idx, other_idx) = border[rng.range(0, border.len() as i32) as usize];
+            build_data.map.tiles[idx] = TileType::Floor;
+            build_data.map.tiles[other_idx] = TileType::Floor;
+            if i % OPENINGS_PER_SNAPSHOT == 0 {
+                build_data.take_snapshot();
+            }
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use rltk::RandomNumberGenerator;
+
+use super::{MetaMapBuilder, BuilderMap, Map, TileType};
+
+const CHUNK_SIZE: i32 = 8;
+const MAX_ATTEMPTS: i32 = 20;
+
+const NORTH: usize = 0;
+const SOUTH: usize = 1;
+const WEST: usize = 2;
+const EAST: usize = 3;
+
+/// A square piece of the sample level, with the patterns allowed next to it on each side
+struct MapChunk {
+    pattern: Vec<TileType>,
+    compatible_with: [Vec<usize>; 4]
+}
+
+/// Cuts the level made so far into chunks, learns which chunks can go next to each other,
+/// and assembles a new level from them
+pub struct WaveformCollapseBuilder {}
+
+impl MetaMapBuilder for WaveformCollapseBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        self.build(rng, build_data);
+    }
+}
+
+impl WaveformCollapseBuilder {
+    pub fn new() -> Box<WaveformCollapseBuilder> {
+        Box::new(WaveformCollapseBuilder{})
+    }
+
+    fn build(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let chunks = build_chunks(&build_data.map);
+        let (chunks_x, chunks_y) = (build_data.map.width / CHUNK_SIZE, build_data.map.height / CHUNK_SIZE);
+        if chunks_x == 0 || chunks_y == 0 { return }
+
+        // A chunk can end up with no pattern fitting all its neighbours, the whole level is
+        // started over when it happens
+        let mut solution = None;
+        for _ in 0..MAX_ATTEMPTS {
+            solution = solve(rng, &chunks, chunks_x, chunks_y);
+            if solution.is_some() { break }
+        }
+        let resolved = match solution {
+            Some(resolved) => resolved,
+            None => return
+        };
+
+        // Nothing the sample placed makes sense in the new level
+        build_data.map = Map::new(build_data.map.depth, build_data.map.width, build_data.map.height);
+        build_data.starting_position = None;
+        build_data.rooms = None;
+        build_data.spawn_list.clear();
+
+        for (cell, pattern) in resolved {
+            let (chunk_x, chunk_y) = (cell as i32 % chunks_x, cell as i32 / chunks_x);
+            for (i, tile) in chunks[pattern].pattern.iter().enumerate() {
+                let x = chunk_x * CHUNK_SIZE + i as i32 % CHUNK_SIZE;
+                let y = chunk_y * CHUNK_SIZE + i as i32 / CHUNK_SIZE;
+                if x < 1 || x > build_data.map.width - 2 || y < 1 || y > build_data.map.height - 2 { continue }
+                build_data.map.set_tile(x, y, *tile);
+            }
+            build_data.take_snapshot();
+        }
+    }
+}
+
+// Every chunk of the sample along with its mirror images, without duplicates
+fn build_chunks(sample: &Map) -> Vec<MapChunk> {
+    let mut patterns: Vec<Vec<TileType>> = Vec::new();
+    for chunk_y in 0..sample.height / CHUNK_SIZE {
+        for chunk_x in 0..sample.width / CHUNK_SIZE {
+            for (flip_x, flip_y) in [(false, false), (true, false), (false, true), (true, true)] {
+                let mut pattern = Vec::new();
+                for dy in 0..CHUNK_SIZE {
+                    for dx in 0..CHUNK_SIZE {
+                        let dx = if flip_x { CHUNK_SIZE - 1 - dx } else { dx };
+                        let dy = if flip_y { CHUNK_SIZE - 1 - dy } else { dy };
+                        let tile = sample.tiles[sample.xy_idx(chunk_x * CHUNK_SIZE + dx, chunk_y * CHUNK_SIZE + dy)];
+                        // The sample's stairs are not kept, the chain places its own
+                        pattern.push(if tile == TileType::StairsDown { TileType::Floor } else { tile });
+                    }
+                }
+                if !patterns.contains(&pattern) {
+                    patterns.push(pattern);
+                }
+            }
+        }
+    }
+
+    let exits: Vec<[Vec<bool>; 4]> = patterns.iter().map(|pattern| pattern_exits(pattern)).collect();
+    patterns.into_iter().enumerate().map(|(i, pattern)| {
+        let compatible_with = [NORTH, SOUTH, WEST, EAST].map(|direction| {
+            (0..exits.len()).filter(|other| sides_match(&exits[i][direction], &exits[*other][opposite(direction)])).collect()
+        });
+        MapChunk{pattern, compatible_with}
+    }).collect()
+}
+
+// Walkable tiles along each side of a pattern
+fn pattern_exits(pattern: &[TileType]) -> [Vec<bool>; 4] {
+    let tile = |x: i32, y: i32| pattern[(y * CHUNK_SIZE + x) as usize].is_walkable();
+    let last = CHUNK_SIZE - 1;
+    [
+        (0..CHUNK_SIZE).map(|x| tile(x, 0)).collect(),
+        (0..CHUNK_SIZE).map(|x| tile(x, last)).collect(),
+        (0..CHUNK_SIZE).map(|y| tile(0, y)).collect(),
+        (0..CHUNK_SIZE).map(|y| tile(last, y)).collect()
+    ]
+}
+
+// Two sides fit when both are closed, or when they share at least one opening
+fn sides_match(side: &[bool], other_side: &[bool]) -> bool {
+    let side_closed = !side.contains(&true);
+    let other_closed = !other_side.contains(&true);
+    if side_closed || other_closed { return side_closed && other_closed }
+    side.iter().zip(other_side.iter()).any(|(open, other_open)| *open && *other_open)
+}
+
+fn opposite(direction: usize) -> usize {
+    match direction {
+        NORTH => SOUTH,
+        SOUTH => NORTH,
+        WEST => EAST,
+        _ => WEST
+    }
+}
+
+// Returns the pattern of every cell in the order they were resolved, None on a contradiction.
+// The next cell is always one of those with the most resolved neighbours, so the level grows
+// from a random first chunk
+fn solve(rng: &mut RandomNumberGenerator, chunks: &[MapChunk], chunks_x: i32, chunks_y: i32) -> Option<Vec<(usize, usize)>> {
+    let nb_cells = (chunks_x * chunks_y) as usize;
+    let mut cells: Vec<Option<usize>> = vec![None; nb_cells];
+    let mut resolved: Vec<(usize, usize)> = Vec::new();
+
+    let first_cell = rng.range(0, nb_cells as i32) as usize;
+    let first_pattern = rng.range(0, chunks.len() as i32) as usize;
+    cells[first_cell] = Some(first_pattern);
+    resolved.push((first_cell, first_pattern));
+
+    while resolved.len() < nb_cells {
+        let neighbours = |cell: usize| -> Vec<(usize, usize)> {
+            let (x, y) = (cell as i32 % chunks_x, cell as i32 / chunks_x);
+            let mut neighbours = Vec::new();
+            if y > 0 { neighbours.push((NORTH, cell - chunks_x as usize)) }
+            if y < chunks_y - 1 { neighbours.push((SOUTH, cell + chunks_x as usize)) }
+            if x > 0 { neighbours.push((WEST, cell - 1)) }
+            if x < chunks_x - 1 { neighbours.push((EAST, cell + 1)) }
+            neighbours.into_iter().filter(|(_direction, neighbour)| cells[*neighbour].is_some()).collect()
+        };
+
+        let candidates: Vec<(usize, Vec<(usize, usize)>)> = (0..nb_cells)
+            .filter(|cell| cells[*cell].is_none())
+            .map(|cell| (cell, neighbours(cell)))
+            .filter(|(_cell, resolved_neighbours)| !resolved_neighbours.is_empty())
+            .collect();
+        let most_neighbours = candidates.iter().map(|(_cell, resolved_neighbours)| resolved_neighbours.len()).max()?;
+        let candidates: Vec<&(usize, Vec<(usize, usize)>)> = candidates.iter().filter(|(_cell, resolved_neighbours)| resolved_neighbours.len() == most_neighbours).collect();
+        let (cell, resolved_neighbours) = candidates[rng.range(0, candidates.len() as i32) as usize];
+
+        let options: Vec<usize> = (0..chunks.len())
+            .filter(|pattern| resolved_neighbours.iter().all(|(direction, neighbour)| {
+                let neighbour_pattern = cells[*neighbour].unwrap();
+                chunks[neighbour_pattern].compatible_with[opposite(*direction)].contains(pattern)
+            }))
+            .collect();
+        if options.is_empty() { return None }
+
+        let pattern = options[rng.range(0, options.len() as i32) as usize];
+        cells[*cell] = Some(pattern);
+        resolved.push((*cell, pattern));
+    }
+
+    Some(resolved)
+}
@@ -1,7 +1,7 @@
 use hecs::*;
 use resources::Resources;
 use crate::map::Map;
-use crate::components::{BlocksTile, Position};
+use crate::components::{BlocksTile, Door, Position};
 
 
 pub fn map_indexing(world: &mut World, res: &mut Resources) {
@@ -9,8 +9,11 @@ pub fn map_indexing(world: &mut World, res: &mut Resources) {
 
     map.set_blocked();
     map.clear_tile_content();
+    for view_blocked in map.view_blocked.iter_mut() {
+        *view_blocked = false;
+    }
 
-    for (id, (_bt, pos)) in world.query_mut::<(Option<&BlocksTile>, &Position)>() {
+    for (id, (_bt, door, pos)) in world.query_mut::<(Option<&BlocksTile>, Option<&Door>, &Position)>() {
         let idx = map.xy_idx(pos.x, pos.y);
         if idx > map.tiles.len() { continue }
 
@@ -18,6 +21,13 @@ pub fn map_indexing(world: &mut World, res: &mut Resources) {
             map.blocked[idx] = true;
         }
 
+        if let Some(door) = door {
+            if !door.open {
+                map.blocked[idx] = true;
+                map.view_blocked[idx] = true;
+            }
+        }
+
         map.tile_content[idx].push(id);
     }
 }
@@ -8,7 +8,7 @@ pub fn melee_combat(world: &mut World, res: &mut Resources) {
     let mut particle_builder = res.get_mut::<ParticleBuilder>().unwrap();
 
     let mut to_remove_wants_melee: Vec<Entity> = vec![];
-    let mut to_add_damage: Vec<(Entity, i32)> = vec![];
+    let mut to_add_damage: Vec<(Entity, i32, String)> = vec![];
 
     for (id, (wants_attack, name, stats)) in &mut world.query::<(&WantsToAttack, &Name, &CombatStats)>() {
         if stats.hp > 0 {
@@ -32,7 +32,7 @@ pub fn melee_combat(world: &mut World, res: &mut Resources) {
                     }
                     else {
                         log.messages.push(format!("{} hits {} for {} hp", &name.name, &target_name.name, damage));
-                        to_add_damage.push((wants_attack.target, damage));
+                        to_add_damage.push((wants_attack.target, damage, name.name.clone()));
                     }
 
                     let pos = &world.get::<Position>(wants_attack.target);
@@ -45,8 +45,8 @@ pub fn melee_combat(world: &mut World, res: &mut Resources) {
         to_remove_wants_melee.push(id);
     }
     
-    for (id, damage) in to_add_damage.iter() {
-        TakeDamage::add_damage(world, *id, *damage);
+    for (id, damage, source) in to_add_damage.iter() {
+        TakeDamage::add_damage(world, *id, *damage, source);
     }
 
     for id in to_remove_wants_melee.iter() {
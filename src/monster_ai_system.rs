@@ -1,9 +1,8 @@
 use hecs::*;
 use resources::Resources;
-use rltk;
 use rltk::Point;
 use crate::{Palette, RunState, particle_system::ParticleBuilder};
-use crate::components::{Position, Monster, Viewshed, WantsToAttack, Confusion};
+use crate::components::{Position, Monster, Viewshed, WantsToAttack, Confusion, Door};
 use crate::map::Map;
 
 
@@ -21,6 +20,22 @@ pub fn monster_ai(world: &mut World, res: &mut Resources) {
 
     let mut needs_wants_to_attack: Vec<Entity> = Vec::new();
     let mut to_update_confusion: Vec<(Entity, Confusion)> = Vec::new();
+    let mut doors_to_open: Vec<Entity> = Vec::new();
+
+    // Monsters look for paths through closed doors, and open them on their way
+    let closed_doors: Vec<usize> = world.query::<(&Door, &Position)>().iter()
+        .filter(|(_id, (door, _pos))| !door.open)
+        .map(|(_id, (_door, pos))| map.xy_idx(pos.x, pos.y))
+        .collect();
+    for idx in closed_doors.iter() {
+        map.blocked[*idx] = false;
+    }
+
+    // They never step on hazardous terrain
+    let hazards: Vec<usize> = (0..map.tiles.len()).filter(|idx| map.tiles[*idx].is_hazard() && !map.blocked[*idx]).collect();
+    for idx in hazards.iter() {
+        map.blocked[*idx] = true;
+    }
 
     // Monster ai
     for (id, (_mon, pos, vs)) in world.query::<(&Monster, &mut Position, &mut Viewshed)>().iter() {
@@ -36,14 +51,22 @@ pub fn monster_ai(world: &mut World, res: &mut Resources) {
         let distance = rltk::DistanceAlg::Pythagoras.distance2d(*ppos, Point::new(pos.x, pos.y));
         if distance < 1.5 {
             needs_wants_to_attack.push(id);
-        } else if vs.visible_tiles.contains(&*ppos){
+        } else if vs.visible_tiles.contains(ppos){
             let path = rltk::a_star_search(
                 map.xy_idx(pos.x, pos.y) as i32,
                 map.xy_idx(ppos.x, ppos.y) as i32,
-                &mut *map
+                &*map
             );
 
-            if path.success && path.steps.len() > 1 {
+            let closed_door = if path.success && path.steps.len() > 1 {
+                map.tile_content[path.steps[1]].iter()
+                    .find(|id| world.get::<Door>(**id).is_ok_and(|door| !door.open))
+                    .copied()
+            } else { None };
+
+            if let Some(door) = closed_door {
+                doors_to_open.push(door);
+            } else if path.success && path.steps.len() > 1 {
                 let (new_x, new_y) = map.idx_xy(path.steps[1]);
                 let mut idx = map.xy_idx(pos.x, pos.y);
                 map.blocked[idx] = false;
@@ -56,6 +79,16 @@ pub fn monster_ai(world: &mut World, res: &mut Resources) {
         }
     }
 
+    for idx in closed_doors.iter() {
+        map.blocked[*idx] = true;
+    }
+    for idx in hazards.iter() {
+        map.blocked[*idx] = false;
+    }
+    for door in doors_to_open {
+        Door::open(world, map, door);
+    }
+
     for id in needs_wants_to_attack.iter() {
         world.insert_one(*id, WantsToAttack {target: *player_id}).unwrap();
     }
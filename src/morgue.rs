@@ -0,0 +1,111 @@
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hecs::*;
+use resources::Resources;
+
+use crate::{RenderOrder, RunStats};
+use crate::components::{CombatStats, Equipped, InBackpack, MeleeDefenseBonus, MeleePowerBonus, Name, Position, Renderable, TakeDamage};
+use crate::gamelog::GameLog;
+use crate::map::{Map, TileType};
+use crate::rng::RunSeed;
+
+const MORGUE_DIR: &str = "./morgue";
+const NB_LOG_MESSAGES: usize = 20;
+
+/// Writes a plain text summary of the run the player just died in
+pub fn write_morgue(world: &World, res: &Resources) -> Result<PathBuf, String> {
+    let player_id = *res.get::<Entity>().unwrap();
+    let name = world.get::<Name>(player_id).map(|name| name.name.clone()).unwrap_or_else(|_| "Player".to_string());
+    let text = morgue_text(world, res, player_id, &name).map_err(|e| e.to_string())?;
+
+    let died_at = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let path = Path::new(MORGUE_DIR).join(format!("morgue_{}_{}.txt", name, died_at));
+    fs::create_dir_all(MORGUE_DIR).map_err(|e| e.to_string())?;
+    fs::write(&path, text).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+fn morgue_text(world: &World, res: &Resources, player_id: Entity, name: &str) -> Result<String, std::fmt::Error> {
+    let map = res.get::<Map>().unwrap();
+    let killer = world.get::<TakeDamage>(player_id).map(|damage| damage.last_source.clone()).unwrap_or_else(|_| "something unknown".to_string());
+    let mut text = String::new();
+
+    writeln!(text, "{} was killed by {} on depth {} after {} turns", name, killer, map.depth, res.get::<RunStats>().unwrap().turns)?;
+    writeln!(text, "Seed: {}", res.get::<RunSeed>().unwrap().0)?;
+
+    writeln!(text, "\nStats")?;
+    if let Ok(stats) = world.get::<CombatStats>(player_id) {
+        writeln!(text, "  HP: {}/{}  Power: {}  Defense: {}", stats.hp, stats.max_hp, stats.power, stats.defense)?;
+    }
+
+    writeln!(text, "\nEquipment")?;
+    for (id, (equipped, item_name)) in world.query::<(&Equipped, &Name)>().iter() {
+        if equipped.owner != player_id { continue }
+        let mut bonuses = Vec::new();
+        if let Ok(bonus) = world.get::<MeleePowerBonus>(id) { bonuses.push(format!("+{} power", bonus.power)); }
+        if let Ok(bonus) = world.get::<MeleeDefenseBonus>(id) { bonuses.push(format!("+{} defense", bonus.defense)); }
+        if bonuses.is_empty() {
+            writeln!(text, "  {}", item_name.name)?;
+        } else {
+            writeln!(text, "  {} ({})", item_name.name, bonuses.join(", "))?;
+        }
+    }
+
+    writeln!(text, "\nBackpack")?;
+    for (_id, (pack, item_name)) in world.query::<(&InBackpack, &Name)>().iter() {
+        if pack.owner == player_id {
+            writeln!(text, "  {}", item_name.name)?;
+        }
+    }
+
+    writeln!(text, "\nLast messages")?;
+    let log = res.get::<GameLog>().unwrap();
+    let first_message = log.messages.len().saturating_sub(NB_LOG_MESSAGES);
+    for message in log.messages[first_message..].iter() {
+        writeln!(text, "  {}", message)?;
+    }
+
+    writeln!(text, "\nMap")?;
+    for line in map_dump(world, &map) {
+        writeln!(text, "{}", line)?;
+    }
+
+    Ok(text)
+}
+
+// Revealed part of the map with the entities the player can see drawn on top, rows that
+// were never explored are left out
+fn map_dump(world: &World, map: &Map) -> Vec<String> {
+    let mut chars: Vec<char> = map.tiles.iter().enumerate().map(|(idx, tile)| {
+        if !map.revealed_tiles[idx] { return ' ' }
+        match tile {
+            TileType::Floor => '.',
+            TileType::Wall => '#',
+            TileType::StairsDown => '>',
+            TileType::StairsUp => '<',
+            TileType::Grass => '"',
+            TileType::ShallowWater => '~',
+            TileType::DeepWater => '≈',
+            TileType::Lava => '^',
+            TileType::Bridge => '='
+        }
+    }).collect();
+
+    let mut query = world.query::<(&Position, &Renderable)>();
+    let mut to_render = query.iter().filter(|(_id, (_pos, render))| render.render && render.order != RenderOrder::Particle).collect::<Vec<_>>();
+    to_render.sort_by_key(|a| a.1.1.order);
+    for (_id, (pos, render)) in to_render {
+        let idx = map.xy_idx(pos.x, pos.y);
+        if idx < chars.len() && map.visible_tiles[idx] {
+            chars[idx] = rltk::to_char(render.glyph as u8);
+        }
+    }
+
+    let rows: Vec<String> = chars.chunks(map.width as usize).map(|row| row.iter().collect::<String>().trim_end().to_string()).collect();
+    let first_row = rows.iter().position(|row| !row.is_empty()).unwrap_or(rows.len());
+    let last_row = rows.iter().rposition(|row| !row.is_empty()).map(|row| row + 1).unwrap_or(first_row);
+    rows[first_row..last_row].to_vec()
+}
@@ -0,0 +1,37 @@
+use hecs::*;
+use resources::Resources;
+use rltk::RandomNumberGenerator;
+use crate::RunState;
+use crate::components::{Position, Npc, Viewshed};
+use crate::map::Map;
+
+/// Peaceful characters stroll around at random, they stay off blocked and hazardous tiles
+pub fn npc_ai(world: &mut World, res: &mut Resources) {
+    let runstate: &RunState = &res.get::<RunState>().unwrap();
+    if *runstate != RunState::MonsterTurn { return; }
+
+    let map: &mut Map = &mut res.get_mut::<Map>().unwrap();
+    let mut rng = res.get_mut::<RandomNumberGenerator>().unwrap();
+
+    for (_id, (_npc, pos, vs)) in world.query::<(&Npc, &mut Position, Option<&mut Viewshed>)>().iter() {
+        // Half of the time they just stand there
+        if rng.roll_dice(1, 2) == 1 { continue }
+
+        let (new_x, new_y) = match rng.roll_dice(1, 4) {
+            1 => (pos.x - 1, pos.y),
+            2 => (pos.x + 1, pos.y),
+            3 => (pos.x, pos.y - 1),
+            _ => (pos.x, pos.y + 1)
+        };
+        if new_x < 1 || new_x > map.width - 2 || new_y < 1 || new_y > map.height - 2 { continue }
+        let new_idx = map.xy_idx(new_x, new_y);
+        if map.blocked[new_idx] || map.tiles[new_idx].is_hazard() { continue }
+
+        let idx = map.xy_idx(pos.x, pos.y);
+        map.blocked[idx] = false;
+        map.blocked[new_idx] = true;
+        pos.x = new_x;
+        pos.y = new_y;
+        if let Some(vs) = vs { vs.dirty = true; }
+    }
+}
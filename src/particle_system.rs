@@ -1,6 +1,6 @@
 use hecs::{Entity, World};
 use resources::Resources;
-use rltk::{FontCharType, RGB, Rltk};
+use rltk::{FontCharType, RGB};
 
 use crate::{RenderOrder, components::{Lifetime, Particle, Position, Renderable, Velocity}};
 
@@ -24,6 +24,7 @@ impl ParticleBuilder {
         ParticleBuilder{ requests: Vec::new() }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn request(&mut self, x: i32, y: i32, vel_x: f32, vel_y: f32, fg: RGB, bg: RGB, glyph: FontCharType, lifetime_ms: f32) {
         self.requests.push(
             ParticleRequest {
@@ -51,15 +52,15 @@ pub fn spawn_particles(world: &mut World, res: &mut Resources) {
     particle_builder.clear();
 }
 
-pub fn update_particles(world: &mut World, _res: &mut Resources, ctx: &Rltk) {
+pub fn update_particles(world: &mut World, _res: &mut Resources, frame_time_ms: f32) {
     for (id, (particle, lifetime)) in world.query::<(&mut Particle, &mut Lifetime)>().iter() {
-        lifetime.ms -= ctx.frame_time_ms;
+        lifetime.ms -= frame_time_ms;
 
         let vel = world.get::<Velocity>(id);
         if let Ok(vel) = vel {
             let mut pos = world.get_mut::<Position>(id).unwrap();
-            particle.float_x += (vel.x) * (ctx.frame_time_ms / 1000.0);
-            particle.float_y += (vel.y) * (ctx.frame_time_ms / 1000.0);
+            particle.float_x += (vel.x) * (frame_time_ms / 1000.0);
+            particle.float_y += (vel.y) * (frame_time_ms / 1000.0);
             pos.x = particle.float_x as i32;
             pos.y = particle.float_y as i32;
         }
@@ -1,21 +1,25 @@
-use std::cmp::{max, min};
 use rltk::{Rltk, VirtualKeyCode, Point};
 use hecs::*;
 use resources::*;
 
 use crate::{State, RunState};
 use crate::map::{Map, TileType};
-use crate::components::{Position, Player, Viewshed, CombatStats, WantsToAttack, Item, WantsToPickupItem};
+use crate::components::{Position, Player, Viewshed, CombatStats, WantsToAttack, Item, WantsToPickupItem, Door, Npc};
 use crate::gamelog::GameLog;
+use crate::replay::Command;
 
 pub fn try_move_player(dx: i32, dy: i32, gs: &mut State) {
-    let map = gs.resources.get::<Map>().unwrap();
+    let mut map = gs.resources.get_mut::<Map>().unwrap();
     let mut needs_wants_to_attack: Option<(Entity, WantsToAttack)> = None;
+    let mut door_to_open: Option<Entity> = None;
 
     for (id, (pos, _player, vs)) in &mut gs.world.query::<(&mut Position, &Player, &mut Viewshed)>().iter() {
         let dest_idx = map.xy_idx(pos.x + dx, pos.y + dy);
 
         for potential_target in map.tile_content[dest_idx].iter() {
+            // Bumping into someone peaceful does not attack them
+            if gs.world.get::<Npc>(*potential_target).is_ok() { continue }
+
             let target_cs = &gs.world.get::<CombatStats>(*potential_target);
             match target_cs {
                 Ok(_cs) => {
@@ -24,14 +28,25 @@ pub fn try_move_player(dx: i32, dy: i32, gs: &mut State) {
                 }
                 Err(_e) => {}
             }
+
+            // Bumping into a closed door opens it
+            if let Ok(door) = gs.world.get::<Door>(*potential_target) {
+                if !door.open { door_to_open = Some(*potential_target); }
+            }
         }
 
         if !map.blocked[dest_idx] {
-            pos.x = min(79, max(0, pos.x + dx));
-            pos.y = min(49, max(0, pos.y + dy));
+            let previous_theme = map.room_theme(pos.x, pos.y);
+            pos.x = (pos.x + dx).clamp(0, map.width - 1);
+            pos.y = (pos.y + dy).clamp(0, map.height - 1);
 
             vs.dirty = true;
 
+            if let Some(theme) = map.room_theme(pos.x, pos.y).filter(|theme| Some(*theme) != previous_theme) {
+                let mut log = gs.resources.get_mut::<GameLog>().unwrap();
+                log.messages.push(format!("You enter the {}", theme.name().to_lowercase()));
+            }
+
             let mut ppos = gs.resources.get_mut::<rltk::Point>().unwrap();
             ppos.x = pos.x;
             ppos.y = pos.y;
@@ -41,6 +56,10 @@ pub fn try_move_player(dx: i32, dy: i32, gs: &mut State) {
     if let Some(v) = needs_wants_to_attack {
         let _res = gs.world.insert_one(v.0, v.1);
     }
+
+    if let Some(door) = door_to_open {
+        Door::open(&mut gs.world, &mut map, door);
+    }
 }
 
 pub fn get_item(world: &mut World, res: &mut Resources){
@@ -57,7 +76,7 @@ pub fn get_item(world: &mut World, res: &mut Resources){
     }
 
     match target_item {
-        None => {log.messages.push(format!("There is nothing to pick up here"))}
+        None => {log.messages.push("There is nothing to pick up here".to_string())}
         Some(item) => {
             let _res = world.insert_one(*player_id, WantsToPickupItem {
                 collected_by: *player_id,
@@ -76,7 +95,21 @@ fn try_next_level(_world: &mut World, res: &mut Resources) -> bool {
     }
     else {
         let mut log = res.get_mut::<GameLog>().unwrap();
-        log.messages.push(format!("There is no stairs down here"));
+        log.messages.push("There is no stairs down here".to_string());
+        false
+    }
+}
+
+fn try_previous_level(_world: &mut World, res: &mut Resources) -> bool {
+    let player_pos = res.get::<Point>().unwrap();
+    let map = res.get::<Map>().unwrap();
+    let player_idx = map.xy_idx(player_pos.x, player_pos.y);
+    if map.tiles[player_idx] == TileType::StairsUp {
+        true
+    }
+    else {
+        let mut log = res.get_mut::<GameLog>().unwrap();
+        log.messages.push("There is no stairs up here".to_string());
         false
     }
 }
@@ -88,27 +121,42 @@ fn skip_turn(world: &mut World, res: &mut Resources) -> RunState {
     RunState::PlayerTurn
 }
 
-pub fn player_input(gs: &mut State, ctx: &mut Rltk) -> RunState {
+pub fn player_input(ctx: &Rltk) -> Option<Command> {
     match ctx.key {
-        None => { return RunState::AwaitingInput }
+        None => None,
         Some(key) => match key {
-            VirtualKeyCode::Left => try_move_player(-1, 0, gs),
-            VirtualKeyCode::Right => try_move_player(1, 0, gs),
-            VirtualKeyCode::Up => try_move_player(0, -1, gs),
-            VirtualKeyCode::Down => try_move_player(0, 1, gs),
-            VirtualKeyCode::Y => try_move_player(-1, -1, gs),
-            VirtualKeyCode::U => try_move_player(1, -1, gs),
-            VirtualKeyCode::N => try_move_player(1, 1, gs),
-            VirtualKeyCode::B => try_move_player(-1, 1, gs),
-            VirtualKeyCode::G => get_item(&mut gs.world, &mut gs.resources),
-            VirtualKeyCode::I => return RunState::ShowInventory,
-            VirtualKeyCode::W => return skip_turn(&mut gs.world, &mut gs.resources),
-            VirtualKeyCode::Escape => return RunState::SaveGame,
-            VirtualKeyCode::Period => {
-                if try_next_level(&mut gs.world, &mut gs.resources) { return RunState::NextLevel; }
-            }
-            _ => { return RunState::AwaitingInput }
+            VirtualKeyCode::Left => Some(Command::Move{dx: -1, dy: 0}),
+            VirtualKeyCode::Right => Some(Command::Move{dx: 1, dy: 0}),
+            VirtualKeyCode::Up => Some(Command::Move{dx: 0, dy: -1}),
+            VirtualKeyCode::Down => Some(Command::Move{dx: 0, dy: 1}),
+            VirtualKeyCode::Y => Some(Command::Move{dx: -1, dy: -1}),
+            VirtualKeyCode::U => Some(Command::Move{dx: 1, dy: -1}),
+            VirtualKeyCode::N => Some(Command::Move{dx: 1, dy: 1}),
+            VirtualKeyCode::B => Some(Command::Move{dx: -1, dy: 1}),
+            VirtualKeyCode::G => Some(Command::PickUp),
+            VirtualKeyCode::I => Some(Command::OpenInventory),
+            VirtualKeyCode::W => Some(Command::Wait),
+            VirtualKeyCode::Period => Some(Command::Descend),
+            VirtualKeyCode::Comma => Some(Command::Ascend),
+            _ => None
         }
     }
-    RunState::PlayerTurn
+}
+
+/// Applies a command given while awaiting input, returns None if it cannot be used here
+pub fn apply_command(gs: &mut State, command: Command) -> Option<RunState> {
+    match command {
+        Command::Move{dx, dy} => try_move_player(dx, dy, gs),
+        Command::PickUp => get_item(&mut gs.world, &mut gs.resources),
+        Command::OpenInventory => return Some(RunState::ShowInventory),
+        Command::Wait => return Some(skip_turn(&mut gs.world, &mut gs.resources)),
+        Command::Descend => {
+            if try_next_level(&mut gs.world, &mut gs.resources) { return Some(RunState::NextLevel); }
+        }
+        Command::Ascend => {
+            if try_previous_level(&mut gs.world, &mut gs.resources) { return Some(RunState::PreviousLevel); }
+        }
+        _ => return None
+    }
+    Some(RunState::PlayerTurn)
 }
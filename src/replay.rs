@@ -0,0 +1,144 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hecs::*;
+use resources::Resources;
+use rltk::{Point, VirtualKeyCode};
+use serde::{Serialize, Deserialize};
+
+use crate::components::CombatStats;
+use crate::map::Map;
+use crate::rng::RunSeed;
+
+const REPLAY_DIR: &str = "./replays";
+pub const DEFAULT_REPLAY_SPEED: f32 = 10.0;
+
+/// Every action the player can take while the game waits for input
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Command {
+    Move {dx: i32, dy: i32},
+    PickUp,
+    Wait,
+    OpenInventory,
+    Descend,
+    Ascend,
+    SelectItem {index: usize},
+    UseItem,
+    DropItem,
+    UnequipItem,
+    Target {x: i32, y: i32},
+    Cancel
+}
+
+/// State of the game when a command was accepted, compared during a replay to detect divergences
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub depth: i32,
+    pub x: i32,
+    pub y: i32,
+    pub hp: i32
+}
+
+impl Checkpoint {
+    pub fn new(world: &World, res: &Resources) -> Checkpoint {
+        let player_id = res.get::<Entity>().unwrap();
+        let player_pos = res.get::<Point>().unwrap();
+        let hp = world.get::<CombatStats>(*player_id).map(|stats| stats.hp).unwrap_or(0);
+        Checkpoint {
+            depth: res.get::<Map>().unwrap().depth,
+            x: player_pos.x,
+            y: player_pos.y,
+            hp
+        }
+    }
+}
+
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct RecordedCommand {
+    pub checkpoint: Checkpoint,
+    pub command: Command
+}
+
+/// Every command of the current run since the start of the game
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Recording {
+    pub seed: RunSeed,
+    pub commands: Vec<RecordedCommand>
+}
+
+impl Recording {
+    pub fn new(seed: RunSeed) -> Recording {
+        Recording{seed, commands: Vec::new()}
+    }
+
+    pub fn record(&mut self, checkpoint: Checkpoint, command: Command) {
+        self.commands.push(RecordedCommand{checkpoint, command});
+    }
+
+    pub fn write(&self) -> Result<PathBuf, String> {
+        let saved_at = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let path = Path::new(REPLAY_DIR).join(format!("replay_{}_{}.json", self.seed.0, saved_at));
+        let json = serde_json::to_string(self).map_err(|e| e.to_string())?;
+        fs::create_dir_all(REPLAY_DIR).map_err(|e| e.to_string())?;
+        fs::write(&path, json).map_err(|e| e.to_string())?;
+        Ok(path)
+    }
+
+    pub fn read(path: &str) -> Result<Recording, String> {
+        let json = fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+        serde_json::from_str(&json).map_err(|e| format!("Invalid replay file {}: {}", path, e))
+    }
+}
+
+/// A recording being played back, commands are fed at `speed` commands per second
+pub struct Replay {
+    pub recording: Recording,
+    pub next: usize,
+    pub speed: f32,
+    pub paused: bool,
+    pub timer: f32,
+    pub expected: Option<Checkpoint>,
+    pub report: Option<String>
+}
+
+impl Replay {
+    pub fn new(recording: Recording, speed: f32) -> Replay {
+        Replay{recording, next: 0, speed, paused: false, timer: 0.0, expected: None, report: None}
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.recording.commands.len()
+    }
+
+    /// Space pauses the replay, + and - change its speed and escape stops it
+    pub fn playback_input(&mut self, key: Option<VirtualKeyCode>) {
+        match key {
+            Some(VirtualKeyCode::Space) => self.paused = !self.paused,
+            Some(VirtualKeyCode::Equals) | Some(VirtualKeyCode::NumpadAdd) => self.speed = f32::min(self.speed * 2.0, 1000.0),
+            Some(VirtualKeyCode::Minus) | Some(VirtualKeyCode::NumpadSubtract) => self.speed = f32::max(self.speed / 2.0, 0.5),
+            Some(VirtualKeyCode::Escape) => self.stop(format!("Replay stopped after {} of {} commands", self.next, self.recording.commands.len())),
+            _ => {}
+        }
+    }
+
+    /// Returns the next command once it is time to play it
+    pub fn next_command(&mut self, frame_time_ms: f32) -> Option<Command> {
+        if self.paused || self.is_finished() || self.report.is_some() { return None }
+
+        self.timer += frame_time_ms;
+        if self.timer < 1000.0 / self.speed { return None }
+        self.timer = 0.0;
+
+        let recorded = self.recording.commands[self.next];
+        self.next += 1;
+        self.expected = Some(recorded.checkpoint);
+        Some(recorded.command)
+    }
+
+    pub fn stop(&mut self, report: String) {
+        if self.report.is_none() {
+            self.report = Some(report);
+        }
+    }
+}
@@ -0,0 +1,35 @@
+use rltk::RandomNumberGenerator;
+use serde::{Serialize, Deserialize};
+
+/// Seeds are kept short enough to be read and typed by the player
+pub const MAX_SEED: u64 = 100_000_000;
+
+/// Seed of the current run, every random stream of the game is derived from it
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunSeed(pub u64);
+
+impl RunSeed {
+    pub fn random() -> RunSeed {
+        RunSeed(RandomNumberGenerator::new().range(0, MAX_SEED))
+    }
+}
+
+#[derive(Copy, Clone)]
+pub enum RandomStream {
+    Map,
+    Combat
+}
+
+/// Creates the generator of one stream of the run. Each depth gets its own map stream so a level
+/// is the same no matter what happened on the levels before it
+pub fn stream_rng(seed: RunSeed, stream: RandomStream, depth: i32) -> RandomNumberGenerator {
+    let stream_id = ((stream as u64) << 32) | depth as u32 as u64;
+    RandomNumberGenerator::seeded(splitmix64(seed.0 ^ splitmix64(stream_id)))
+}
+
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
@@ -0,0 +1,217 @@
+use rltk::RandomNumberGenerator;
+use serde_json::{Map, Value, json};
+
+use crate::saveload_system::{SAVE_VERSION, SaveError};
+
+type Migration = fn(&mut Map<String, Value>) -> Result<(), SaveError>;
+
+// MIGRATIONS[i] upgrades a save from version i to version i + 1
+const MIGRATIONS: [Migration; SAVE_VERSION as usize] = [
+    add_combat_stats_regen_rate,
+    add_summary,
+    add_dungeon,
+    add_seed,
+    add_recording,
+    add_turns,
+    add_run_stats,
+    add_slot,
+    add_room_themes
+];
+
+/// Upgrades a save written by any older version of the game to `SAVE_VERSION`, one version at a time
+pub fn migrate(save: &mut Value) -> Result<(), SaveError> {
+    let save = save.as_object_mut().ok_or_else(|| corrupted("the save is not an object"))?;
+
+    // Saves written before the header existed are version 0
+    let version = match save.get("header") {
+        None => 0,
+        Some(header) => {
+            let version = header.get("version").and_then(Value::as_u64).ok_or_else(|| corrupted("the header has no version"))?;
+            version as u32
+        }
+    };
+    if version > SAVE_VERSION { return Err(SaveError::UnsupportedVersion(version)) }
+
+    for (from_version, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        migration(save)?;
+        save.insert("header".to_string(), json!({"version": from_version as u32 + 1}));
+    }
+
+    Ok(())
+}
+
+fn corrupted(message: &str) -> SaveError {
+    SaveError::Corrupted(message.to_string())
+}
+
+fn entities(save: &mut Map<String, Value>) -> Result<&mut Map<String, Value>, SaveError> {
+    save.get_mut("entities").and_then(Value::as_object_mut).ok_or_else(|| corrupted("there are no entities"))
+}
+
+// Version 0 -> 1: CombatStats gained a regen_rate, the player regenerates 1 hp per skipped turn
+fn add_combat_stats_regen_rate(save: &mut Map<String, Value>) -> Result<(), SaveError> {
+    for components in entities(save)?.values_mut() {
+        let components = components.as_object_mut().ok_or_else(|| corrupted("an entity is not an object"))?;
+        let is_player = components.contains_key("Player");
+        if let Some(stats) = components.get_mut("CombatStats").and_then(Value::as_object_mut) {
+            let regen_rate = if is_player { 1 } else { 0 };
+            stats.entry("regen_rate").or_insert_with(|| json!(regen_rate));
+        }
+    }
+    Ok(())
+}
+
+// Version 1 -> 2: saves store a summary of the player for the slot picker
+fn add_summary(save: &mut Map<String, Value>) -> Result<(), SaveError> {
+    let depth = save.get("map").and_then(|map| map.get("depth")).cloned().ok_or_else(|| corrupted("the map has no depth"))?;
+    let player = entities(save)?.values().find(|components| components.get("Player").is_some()).ok_or_else(|| corrupted("there is no player"))?;
+    let name = player.pointer("/Name/name").cloned().unwrap_or_else(|| json!("Unknown"));
+    let hp = player.pointer("/CombatStats/hp").cloned().unwrap_or_else(|| json!(0));
+    let max_hp = player.pointer("/CombatStats/max_hp").cloned().unwrap_or_else(|| json!(0));
+
+    let summary = json!({"name": name, "depth": depth, "hp": hp, "max_hp": max_hp, "saved_at": 0});
+    save.insert("summary".to_string(), summary);
+    Ok(())
+}
+
+// Version 2 -> 3: visited levels are kept in the dungeon
+fn add_dungeon(save: &mut Map<String, Value>) -> Result<(), SaveError> {
+    save.insert("dungeon".to_string(), json!({"maps": {}}));
+    Ok(())
+}
+
+// Version 3 -> 4: runs are seeded, older runs get seed 0 for the levels they have not visited yet
+fn add_seed(save: &mut Map<String, Value>) -> Result<(), SaveError> {
+    let rng = serde_json::to_value(RandomNumberGenerator::seeded(0))?;
+    save.insert("seed".to_string(), json!(0));
+    save.insert("rng".to_string(), rng);
+    Ok(())
+}
+
+// Version 4 -> 5: the commands of the run are saved to write its replay, older runs cannot be replayed
+fn add_recording(save: &mut Map<String, Value>) -> Result<(), SaveError> {
+    save.insert("recording".to_string(), Value::Null);
+    Ok(())
+}
+
+// Version 5 -> 6: the turns of the run are counted, older runs start counting from the load
+fn add_turns(save: &mut Map<String, Value>) -> Result<(), SaveError> {
+    save.insert("turns".to_string(), json!(0));
+    Ok(())
+}
+
+// Version 6 -> 7: the turn count is part of the run stats, the kills of older runs are unknown
+fn add_run_stats(save: &mut Map<String, Value>) -> Result<(), SaveError> {
+    let turns = save.remove("turns").unwrap_or_else(|| json!(0));
+    let depth = save.get("map").and_then(|map| map.get("depth")).and_then(Value::as_i64).ok_or_else(|| corrupted("the map has no depth"))?;
+    let deepest_visited = save.get("dungeon").and_then(|dungeon| dungeon.get("maps")).and_then(Value::as_object)
+        .map(|maps| maps.keys().filter_map(|depth| depth.parse::<i64>().ok()).max().unwrap_or(depth))
+        .unwrap_or(depth);
+
+    let stats = json!({"turns": turns, "monsters_killed": 0, "deepest_depth": i64::max(depth, deepest_visited)});
+    save.insert("stats".to_string(), stats);
+    Ok(())
+}
+
+// Version 7 -> 8: saves know their slot so the autosave can be saved back to it, slot files
+// are loaded with the slot they are in
+fn add_slot(save: &mut Map<String, Value>) -> Result<(), SaveError> {
+    save.insert("slot".to_string(), json!(0));
+    Ok(())
+}
+
+// Version 8 -> 9: maps know which of their rooms are themed, the rooms of older maps are not
+fn add_room_themes(save: &mut Map<String, Value>) -> Result<(), SaveError> {
+    let map = save.get_mut("map").and_then(Value::as_object_mut).ok_or_else(|| corrupted("there is no map"))?;
+    map.insert("room_themes".to_string(), json!([]));
+
+    let maps = save.get_mut("dungeon").and_then(|dungeon| dungeon.get_mut("maps")).and_then(Value::as_object_mut)
+        .ok_or_else(|| corrupted("the dungeon has no maps"))?;
+    for map in maps.values_mut() {
+        let map = map.as_object_mut().ok_or_else(|| corrupted("a level of the dungeon is not an object"))?;
+        map.insert("room_themes".to_string(), json!([]));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A save from before the header existed, with only what the migrations read
+    fn version_0_save() -> Value {
+        json!({
+            "map": {"depth": 3},
+            "entities": {
+                "1": {"Player": {}, "Name": {"name": "Blabinou"}, "CombatStats": {"max_hp": 30, "hp": 21, "defense": 2, "power": 5}},
+                "2": {"Monster": {}, "CombatStats": {"max_hp": 8, "hp": 8, "defense": 1, "power": 4}}
+            }
+        })
+    }
+
+    #[test]
+    fn old_save_is_migrated_to_the_current_version() {
+        let mut save = version_0_save();
+        assert!(migrate(&mut save).is_ok());
+
+        assert_eq!(save["header"]["version"], json!(SAVE_VERSION));
+        // 0 -> 1
+        assert_eq!(save["entities"]["1"]["CombatStats"]["regen_rate"], json!(1));
+        assert_eq!(save["entities"]["2"]["CombatStats"]["regen_rate"], json!(0));
+        // 1 -> 2
+        assert_eq!(save["summary"], json!({"name": "Blabinou", "depth": 3, "hp": 21, "max_hp": 30, "saved_at": 0}));
+        // 2 -> 3
+        assert_eq!(save["dungeon"], json!({"maps": {}}));
+        // 3 -> 4
+        assert_eq!(save["seed"], json!(0));
+        assert!(save["rng"].is_object());
+        // 4 -> 5
+        assert_eq!(save["recording"], Value::Null);
+        // 5 -> 6 and 6 -> 7, the turns are moved into the stats
+        assert!(save.get("turns").is_none());
+        assert_eq!(save["stats"], json!({"turns": 0, "monsters_killed": 0, "deepest_depth": 3}));
+        // 7 -> 8
+        assert_eq!(save["slot"], json!(0));
+        // 8 -> 9
+        assert_eq!(save["map"]["room_themes"], json!([]));
+    }
+
+    #[test]
+    fn migrations_start_from_the_version_of_the_save() {
+        let mut save = version_0_save();
+        save["header"] = json!({"version": 6});
+        save["turns"] = json!(42);
+        save["dungeon"] = json!({"maps": {"5": {}}});
+        assert!(migrate(&mut save).is_ok());
+
+        // The earlier migrations were skipped
+        assert!(save.get("summary").is_none());
+        assert!(save["entities"]["1"]["CombatStats"].get("regen_rate").is_none());
+        assert_eq!(save["stats"], json!({"turns": 42, "monsters_killed": 0, "deepest_depth": 5}));
+        assert_eq!(save["dungeon"]["maps"]["5"]["room_themes"], json!([]));
+        assert_eq!(save["header"]["version"], json!(SAVE_VERSION));
+    }
+
+    #[test]
+    fn current_save_is_left_untouched() {
+        let mut save = version_0_save();
+        save["header"] = json!({"version": SAVE_VERSION});
+        let before = save.clone();
+        assert!(migrate(&mut save).is_ok());
+        assert_eq!(save, before);
+    }
+
+    #[test]
+    fn newer_save_is_rejected() {
+        let mut save = version_0_save();
+        save["header"] = json!({"version": SAVE_VERSION + 1});
+        assert!(matches!(migrate(&mut save), Err(SaveError::UnsupportedVersion(v)) if v == SAVE_VERSION + 1));
+    }
+
+    #[test]
+    fn save_without_a_version_in_its_header_is_rejected() {
+        let mut save = version_0_save();
+        save["header"] = json!({});
+        assert!(matches!(migrate(&mut save), Err(SaveError::Corrupted(_))));
+    }
+}
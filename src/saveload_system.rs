@@ -0,0 +1,368 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hecs::*;
+use hecs::serialize::row::{DeserializeContext, SerializeContext, deserialize, serialize, try_serialize};
+use resources::Resources;
+use rltk::{Point, RandomNumberGenerator};
+use serde::{Serialize, Deserialize};
+use serde::de::MapAccess;
+use serde::ser::SerializeMap;
+
+use crate::components::{AreaOfEffect, BlocksTile, CombatStats, Confusion, Consumable, DealsDamage, Door, Equippable, Equipped, InBackpack, Item, MeleeDefenseBonus, MeleePowerBonus, Monster, Name, Npc, OtherLevelPosition, Player, Position, ProvidesHealing, Ranged, Renderable, SerializeMe, Viewshed};
+use crate::gamelog::GameLog;
+use crate::dungeon::Dungeon;
+use crate::map::Map;
+use crate::rng::RunSeed;
+use crate::replay::Recording;
+use crate::save_migrations;
+use crate::RunStats;
+
+const SAVE_DIR: &str = "./saves";
+pub const NB_SAVE_SLOTS: usize = 5;
+
+/// Version of the save format written by `save_game`, bump it and add a migration in
+/// `save_migrations` whenever a serialized component or resource changes
+pub const SAVE_VERSION: u32 = 9;
+
+/// Slot the current game is saved to
+#[derive(Copy, Clone)]
+pub struct SaveSlot(pub usize);
+
+/// What is shown for a save in the slot picker, stored in the save so the slots can be
+/// listed without loading them
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SaveSummary {
+    pub name: String,
+    pub depth: i32,
+    pub hp: i32,
+    pub max_hp: i32,
+    /// Seconds since the unix epoch, 0 if unknown
+    pub saved_at: u64
+}
+
+pub enum SlotInfo {
+    Empty,
+    Saved(SaveSummary),
+    Unreadable(String)
+}
+
+pub struct SaveSlotList {
+    pub slots: Vec<SlotInfo>
+}
+
+pub enum SaveError {
+    Io(io::Error),
+    Parse(serde_json::Error),
+    UnsupportedVersion(u32),
+    Corrupted(String)
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Io(e) => write!(f, "Unable to access the save file: {}", e),
+            SaveError::Parse(e) => write!(f, "The save file is invalid: {}", e),
+            SaveError::UnsupportedVersion(v) => write!(f, "The save file version {} is newer than this game (version {})", v, SAVE_VERSION),
+            SaveError::Corrupted(e) => write!(f, "The save file is corrupted: {}", e)
+        }
+    }
+}
+
+impl From<io::Error> for SaveError {
+    fn from(e: io::Error) -> Self { SaveError::Io(e) }
+}
+
+impl From<serde_json::Error> for SaveError {
+    fn from(e: serde_json::Error) -> Self { SaveError::Parse(e) }
+}
+
+// Declares every component that is written to the save file, and generates the
+// component id enum used as key in the save along with the (de)serialization helpers
+macro_rules! serializable_components {
+    ($($type:ident),*) => {
+        #[derive(Serialize, Deserialize)]
+        enum ComponentId { $($type),* }
+
+        fn serialize_components<S: SerializeMap>(entity: &EntityRef<'_>, map: &mut S) -> Result<(), S::Error> {
+            $(try_serialize::<$type, _, _>(entity, &ComponentId::$type, map)?;)*
+            Ok(())
+        }
+
+        fn deserialize_component<'de, M: MapAccess<'de>>(key: ComponentId, map: &mut M, builder: &mut EntityBuilder) -> Result<(), M::Error> {
+            match key {
+                $(ComponentId::$type => { builder.add::<$type>(map.next_value()?); })*
+            }
+            Ok(())
+        }
+    };
+}
+
+serializable_components!(
+    SerializeMe, Position, OtherLevelPosition, Renderable, Player, Viewshed, Monster, Npc, Name, BlocksTile, CombatStats,
+    Item, Equippable, Equipped, InBackpack, Consumable, MeleePowerBonus, MeleeDefenseBonus,
+    ProvidesHealing, Ranged, DealsDamage, Confusion, AreaOfEffect, Door
+);
+
+#[derive(Serialize, Deserialize)]
+struct SaveHeader {
+    version: u32
+}
+
+#[derive(Serialize, Deserialize)]
+struct SaveData {
+    header: SaveHeader,
+    summary: SaveSummary,
+    map: Map,
+    dungeon: Dungeon,
+    log: GameLog,
+    seed: RunSeed,
+    rng: RandomNumberGenerator,
+    recording: Option<Recording>,
+    stats: RunStats,
+    slot: usize,
+    entities: serde_json::Value
+}
+
+struct SaveContext;
+
+impl SerializeContext for SaveContext {
+    fn serialize_entity<S>(&mut self, entity: EntityRef<'_>, map: &mut S) -> Result<(), S::Error>
+    where
+        S: SerializeMap
+    {
+        if entity.get::<SerializeMe>().is_none() { return Ok(()) }
+        serialize_components(&entity, map)
+    }
+}
+
+// Tags an entity of the deserialized world with the id it was respawned as in the game world
+struct Respawned(Entity);
+
+struct LoadContext<'a> {
+    world: &'a mut World,
+    builder: EntityBuilder
+}
+
+impl DeserializeContext for LoadContext<'_> {
+    fn deserialize_entity<'de, M>(&mut self, mut map: M, entity: &mut EntityBuilder) -> Result<(), M::Error>
+    where
+        M: MapAccess<'de>
+    {
+        self.builder.clear();
+        while let Some(key) = map.next_key()? {
+            deserialize_component(key, &mut map, &mut self.builder)?;
+        }
+
+        // Entities without SerializeMe were saved without any component, skip them
+        if self.builder.has::<SerializeMe>() {
+            let new_id = self.world.spawn(self.builder.build());
+            entity.add(Respawned(new_id));
+        }
+        Ok(())
+    }
+}
+
+fn slot_path(slot: usize) -> PathBuf {
+    Path::new(SAVE_DIR).join(format!("slot{}.json", slot + 1))
+}
+
+fn autosave_path() -> PathBuf {
+    Path::new(SAVE_DIR).join("autosave.json")
+}
+
+pub fn does_save_exist() -> bool {
+    (0..NB_SAVE_SLOTS).any(|slot| slot_path(slot).exists())
+}
+
+pub fn does_autosave_exist() -> bool {
+    autosave_path().exists()
+}
+
+/// Which save files exist, looked up when the main menu is entered rather than on every frame
+pub struct SaveFiles {
+    pub autosave: bool,
+    pub saves: bool
+}
+
+impl SaveFiles {
+    pub fn scan() -> SaveFiles {
+        SaveFiles{autosave: does_autosave_exist(), saves: does_save_exist()}
+    }
+}
+
+/// The autosave is only kept until the run is saved to its slot or ends
+pub fn delete_autosave() -> Result<(), SaveError> {
+    let path = autosave_path();
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Writes to a temporary file first and renames it over the destination, so a crash while
+/// writing leaves the previous file untouched. The temporary file is flushed to the disk before
+/// the rename, otherwise a power loss could leave an empty file in its place
+pub fn write_atomically(path: &Path, contents: &str) -> io::Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    let mut file = fs::File::create(&tmp_path)?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&tmp_path, path)
+}
+
+pub fn delete_save(slot: usize) -> Result<(), SaveError> {
+    let path = slot_path(slot);
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+fn read_save(path: &Path) -> Result<serde_json::Value, SaveError> {
+    let json = fs::read_to_string(path)?;
+    let mut save: serde_json::Value = serde_json::from_str(&json)?;
+    save_migrations::migrate(&mut save)?;
+    Ok(save)
+}
+
+fn read_summary(slot: usize) -> Result<SaveSummary, SaveError> {
+    #[derive(Deserialize)]
+    struct SummaryOnly { summary: SaveSummary }
+
+    let summary: SummaryOnly = serde_json::from_value(read_save(&slot_path(slot))?)?;
+    Ok(summary.summary)
+}
+
+pub fn read_save_slots() -> SaveSlotList {
+    let slots = (0..NB_SAVE_SLOTS).map(|slot| {
+        if !slot_path(slot).exists() { return SlotInfo::Empty }
+        match read_summary(slot) {
+            Ok(summary) => SlotInfo::Saved(summary),
+            Err(e) => SlotInfo::Unreadable(e.to_string())
+        }
+    }).collect();
+    SaveSlotList{slots}
+}
+
+fn summarize(world: &World, res: &Resources) -> SaveSummary {
+    let player_id = res.get::<Entity>().unwrap();
+    let name = world.get::<Name>(*player_id).unwrap();
+    let stats = world.get::<CombatStats>(*player_id).unwrap();
+    let saved_at = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+
+    SaveSummary {
+        name: name.name.clone(),
+        depth: res.get::<Map>().unwrap().depth,
+        hp: stats.hp,
+        max_hp: stats.max_hp,
+        saved_at
+    }
+}
+
+pub fn save_game(world: &World, res: &Resources) -> Result<(), SaveError> {
+    let slot = res.get::<SaveSlot>().unwrap().0;
+    write_save(world, res, &slot_path(slot))
+}
+
+/// Saves the current game to the autosave, loaded back from "Continue" in the main menu
+pub fn autosave(world: &World, res: &Resources) -> Result<(), SaveError> {
+    write_save(world, res, &autosave_path())
+}
+
+fn write_save(world: &World, res: &Resources, path: &Path) -> Result<(), SaveError> {
+    let entities = serialize(world, &mut SaveContext, serde_json::value::Serializer)?;
+    let data = SaveData {
+        header: SaveHeader{version: SAVE_VERSION},
+        summary: summarize(world, res),
+        map: res.get::<Map>().unwrap().clone(),
+        dungeon: res.get::<Dungeon>().unwrap().clone(),
+        log: res.get::<GameLog>().unwrap().clone(),
+        seed: *res.get::<RunSeed>().unwrap(),
+        rng: res.get::<RandomNumberGenerator>().unwrap().clone(),
+        recording: res.get::<Recording>().ok().map(|recording| recording.clone()),
+        stats: *res.get::<RunStats>().unwrap(),
+        slot: res.get::<SaveSlot>().unwrap().0,
+        entities
+    };
+
+    let json = serde_json::to_string(&data)?;
+    fs::create_dir_all(SAVE_DIR)?;
+    write_atomically(path, &json)?;
+    Ok(())
+}
+
+pub fn load_game(world: &mut World, res: &mut Resources, slot: usize) -> Result<(), SaveError> {
+    let mut data: SaveData = serde_json::from_value(read_save(&slot_path(slot))?)?;
+    data.slot = slot;
+    load_save_data(world, res, data)
+}
+
+/// Loads the autosave, the game keeps being saved to the slot the run was started in
+pub fn load_autosave(world: &mut World, res: &mut Resources) -> Result<(), SaveError> {
+    let data: SaveData = serde_json::from_value(read_save(&autosave_path())?)?;
+    if data.slot >= NB_SAVE_SLOTS {
+        return Err(SaveError::Corrupted(format!("slot {} does not exist", data.slot + 1)));
+    }
+    load_save_data(world, res, data)
+}
+
+fn load_save_data(world: &mut World, res: &mut Resources, data: SaveData) -> Result<(), SaveError> {
+
+    let mut map = data.map;
+    if map.tiles.len() != (map.width * map.height) as usize || map.revealed_tiles.len() != map.tiles.len() {
+        return Err(SaveError::Corrupted("map size does not match its tiles".to_string()));
+    }
+
+    // Load in a new world so the current one is left untouched if the save is invalid.
+    // The returned world only holds the old ids tagged with their new id
+    let mut new_world = World::new();
+    let old_world = deserialize(&mut LoadContext{world: &mut new_world, builder: EntityBuilder::new()}, data.entities)?;
+    let remap: HashMap<Entity, Entity> = old_world.query::<&Respawned>().iter().map(|(old_id, new_id)| (old_id, new_id.0)).collect();
+    remap_entities(&mut new_world, &remap)?;
+
+    for (_id, vs) in new_world.query_mut::<&mut Viewshed>() {
+        vs.dirty = true;
+    }
+
+    let (player_id, player_pos) = new_world.query::<(&Player, &Position)>().iter().map(|(id, (_p, pos))| (id, *pos)).next()
+        .ok_or_else(|| SaveError::Corrupted("there is no player".to_string()))?;
+
+    *world = new_world;
+    map.reset_content();
+    res.insert(map);
+    res.insert(data.dungeon);
+    res.insert(data.log);
+    res.insert(data.seed);
+    res.insert(data.rng);
+    res.insert(data.stats);
+    match data.recording {
+        Some(recording) => { res.insert(recording); }
+        None => { res.remove::<Recording>(); }
+    }
+    res.insert(player_id);
+    res.insert(Point::new(player_pos.x, player_pos.y));
+    res.insert(SaveSlot(data.slot));
+
+    Ok(())
+}
+
+fn remap_entity(remap: &HashMap<Entity, Entity>, id: Entity) -> Result<Entity, SaveError> {
+    remap.get(&id).copied().ok_or_else(|| SaveError::Corrupted(format!("reference to unknown entity {}", id.to_bits())))
+}
+
+fn remap_entities(world: &mut World, remap: &HashMap<Entity, Entity>) -> Result<(), SaveError> {
+    for (_id, backpack) in world.query_mut::<&mut InBackpack>() {
+        backpack.owner = remap_entity(remap, backpack.owner)?;
+    }
+
+    for (_id, equipped) in world.query_mut::<&mut Equipped>() {
+        equipped.owner = remap_entity(remap, equipped.owner)?;
+    }
+
+    Ok(())
+}
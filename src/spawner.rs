@@ -1,11 +1,9 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 use hecs::*;
-use resources::*;
 use rltk::RandomNumberGenerator;
-use crate::components::{AreaOfEffect, BlocksTile, CombatStats, Confusion, Consumable, DealsDamage, EquipmentSlot, Equippable, Item, MeleeDefenseBonus, MeleePowerBonus, Monster, Name, Player, Position, ProvidesHealing, Ranged, Renderable, SerializeMe, Viewshed};
+use crate::components::{AreaOfEffect, BlocksTile, CombatStats, Confusion, Consumable, DealsDamage, Door, EquipmentSlot, Equippable, Item, MeleeDefenseBonus, MeleePowerBonus, Monster, Name, Npc, Player, Position, ProvidesHealing, Ranged, Renderable, SerializeMe, Viewshed};
 use crate::{Palette, RenderOrder};
-use crate::rect::Rect;
 use crate::weighted_table::WeightedTable;
 
 const MAX_SPAWNS: i32 = 8;
@@ -33,68 +31,86 @@ pub fn player(world: &mut World, pos: (i32, i32)) -> Entity {
     ))
 }
 
-pub fn room_table(depth: i32) -> WeightedTable {
-    WeightedTable::new()
-        .add("Goblin", 10)
-        .add("Orc", 1 + depth)
-        .add("Health Potion", 7)
-        .add("Fireball Scroll", 2 + depth)
-        .add("Confusion Scroll", 2 + depth)
-        .add("Magic Missile Scroll", 4)
-        .add("Dagger", 2)
-        .add("Shield", 2)
-        .add("Longsword", depth - 1)
-        .add("Tower Shield", depth - 1)
+/// Picks the monsters and items to spawn on tiles of an area of any shape
+pub fn spawn_region(rng: &mut RandomNumberGenerator, area: &[(i32, i32)], depth: i32, spawn_table: &WeightedTable, spawn_list: &mut Vec<((i32, i32), String)>) {
+    let nb_spawns = rng.range(-2, MAX_SPAWNS + depth);
+    fill_region(rng, area, nb_spawns, spawn_table, spawn_list);
 }
 
-pub fn fill_room(world: &mut World, res: &mut Resources, room: &Rect, depth: i32) {
-    let spawn_table = &room_table(depth);
-    let mut spawn_points: HashMap<(i32, i32), String> = HashMap::new();
+/// Picks `nb_spawns` monsters and items from the table, on as many different tiles of the area
+pub fn fill_region(rng: &mut RandomNumberGenerator, area: &[(i32, i32)], nb_spawns: i32, spawn_table: &WeightedTable, spawn_list: &mut Vec<((i32, i32), String)>) {
+    // Ordered so entities are always spawned in the same order for a given seed
+    let mut spawn_points: BTreeMap<(i32, i32), String> = BTreeMap::new();
     {
-        let mut rng = &mut res.get_mut::<RandomNumberGenerator>().unwrap();
-        let nb_spawns = rng.range(-2, MAX_SPAWNS + depth);
+        let nb_spawns = i32::min(nb_spawns, area.len() as i32);
 
         for _i in 0..nb_spawns {
             let mut added = false;
             while !added {
-                let x = rng.range(room.x1, room.x1 + room.width());
-                let y = rng.range(room.y1, room.y1 + room.height());
-                if !spawn_points.contains_key(&(x, y)) {
-                    spawn_points.insert((x, y), spawn_table.roll(&mut rng).unwrap());
+                let point = area[rng.range(0, area.len() as i32) as usize];
+                if let std::collections::btree_map::Entry::Vacant(e) = spawn_points.entry(point) {
+                    // An empty table spawns nothing
+                    if let Some(name) = spawn_table.roll(rng) {
+                        e.insert(name);
+                    }
                     added = true;
                 }
             }
         }
     }
 
-    for spawn in spawn_points.iter() {
-        let (x, y) = *spawn.0;
-        match spawn.1.as_ref() {
-            "Goblin" => goblin(world, x, y),
-            "Orc" => orc(world, x, y),
-            "Health Potion" => health_potion(world, x, y),
-            "Fireball Scroll" => fireball_scroll(world, x, y),
-            "Confusion Scroll" => confusion_scroll(world, x, y),
-            "Magic Missile Scroll" => magic_missile_scroll(world, x, y),
-            "Dagger" => dagger(world, x, y),
-            "Shield" => shield(world, x, y),
-            "Longsword" => longsword(world, x, y),
-            "Tower Shield" => tower_shield(world, x, y),
-            _ => {}
-        }
+    spawn_list.extend(spawn_points);
+}
+
+/// Every name `spawn_entity` knows, spawn tables are checked against it
+pub const SPAWNABLE_NAMES: &[&str] = &[
+    "Goblin", "Orc", "Orc Warlord", "Health Potion", "Fireball Scroll", "Confusion Scroll", "Magic Missile Scroll",
+    "Dagger", "Shield", "Longsword", "Tower Shield", "Door",
+    "Barkeep", "Patron", "Blacksmith", "Alchemist", "Townsperson",
+    "Gilded Statue", "Bones", "Weapon Rack", "Bookshelf", "Candle"
+];
+
+/// Spawns a monster or an item from its name, unknown names spawn nothing
+pub fn spawn_entity(world: &mut World, (x, y): (i32, i32), name: &str) {
+    match name {
+        "Goblin" => goblin(world, x, y),
+        "Orc" => orc(world, x, y),
+        "Orc Warlord" => orc_warlord(world, x, y),
+        "Health Potion" => health_potion(world, x, y),
+        "Fireball Scroll" => fireball_scroll(world, x, y),
+        "Confusion Scroll" => confusion_scroll(world, x, y),
+        "Magic Missile Scroll" => magic_missile_scroll(world, x, y),
+        "Dagger" => dagger(world, x, y),
+        "Shield" => shield(world, x, y),
+        "Longsword" => longsword(world, x, y),
+        "Tower Shield" => tower_shield(world, x, y),
+        "Door" => door(world, x, y),
+        "Barkeep" | "Patron" | "Blacksmith" | "Alchemist" | "Townsperson" => npc(world, x, y, name.to_string()),
+        "Gilded Statue" => decoration(world, x, y, 'Ω', Palette::COLOR_4, name),
+        "Bones" => decoration(world, x, y, '%', Palette::MAIN_FG, name),
+        "Weapon Rack" => decoration(world, x, y, '╥', Palette::COLOR_3, name),
+        "Bookshelf" => decoration(world, x, y, '≡', Palette::COLOR_3, name),
+        "Candle" => decoration(world, x, y, '¡', Palette::COLOR_4, name),
+        _ => {}
     }
 }
 
 fn orc(world: &mut World, x: i32, y:i32) {
-    monster(world, x, y, rltk::to_cp437('o'), "Orc".to_string());
+    monster(world, x, y, rltk::to_cp437('o'), "Orc".to_string(), CombatStats {max_hp: 8, hp: 8, defense: 1, power: 4, regen_rate: 0});
 }
 
 fn goblin(world: &mut World, x: i32, y:i32) {
-    monster(world, x, y, rltk::to_cp437('g'), "Goblin".to_string());
+    monster(world, x, y, rltk::to_cp437('g'), "Goblin".to_string(), CombatStats {max_hp: 8, hp: 8, defense: 1, power: 4, regen_rate: 0});
 }
 
-fn monster(world: &mut World, x: i32, y: i32, glyph: rltk::FontCharType, name: String) {
+// The boss of the warlord's hall
+fn orc_warlord(world: &mut World, x: i32, y:i32) {
+    monster(world, x, y, rltk::to_cp437('O'), "Orc Warlord".to_string(), CombatStats {max_hp: 40, hp: 40, defense: 3, power: 8, regen_rate: 0});
+}
+
+fn monster(world: &mut World, x: i32, y: i32, glyph: rltk::FontCharType, name: String, stats: CombatStats) {
     world.spawn((
+        SerializeMe {},
         Position {x, y},
         Renderable {
             glyph,
@@ -111,12 +127,47 @@ fn monster(world: &mut World, x: i32, y: i32, glyph: rltk::FontCharType, name: S
         Monster {},
         Name {name},
         BlocksTile {},
-        CombatStats {max_hp: 8, hp: 8, defense: 1, power: 4, regen_rate: 0}
+        stats
+    ));
+}
+
+fn npc(world: &mut World, x: i32, y: i32, name: String) {
+    world.spawn((
+        SerializeMe {},
+        Position {x, y},
+        Renderable {
+            glyph: rltk::to_cp437('☺'),
+            fg: Palette::COLOR_4,
+            bg: Palette::MAIN_BG,
+            order: RenderOrder::NPC,
+            ..Default::default()
+        },
+        Npc {},
+        Name {name},
+        BlocksTile {},
+        CombatStats {max_hp: 8, hp: 8, defense: 0, power: 0, regen_rate: 0}
+    ));
+}
+
+// Furniture of the themed rooms, only there to be looked at
+fn decoration(world: &mut World, x: i32, y: i32, glyph: char, fg: rltk::RGB, name: &str) {
+    world.spawn((
+        SerializeMe {},
+        Position {x, y},
+        Renderable {
+            glyph: rltk::to_cp437(glyph),
+            fg,
+            bg: Palette::MAIN_BG,
+            order: RenderOrder::Items,
+            ..Default::default()
+        },
+        Name {name: name.to_string()}
     ));
 }
 
 fn health_potion(world: &mut World, x: i32, y:i32) {
     world.spawn((
+        SerializeMe {},
         Position {x, y},
         Renderable {
             glyph: rltk::to_cp437('p'),
@@ -134,6 +185,7 @@ fn health_potion(world: &mut World, x: i32, y:i32) {
 
 fn magic_missile_scroll(world: &mut World, x: i32, y:i32) {
     world.spawn((
+        SerializeMe {},
         Position {x, y},
         Renderable {
             glyph: rltk::to_cp437('('),
@@ -152,6 +204,7 @@ fn magic_missile_scroll(world: &mut World, x: i32, y:i32) {
 
 fn fireball_scroll(world: &mut World, x: i32, y: i32) {
     world.spawn((
+        SerializeMe {},
         Position {x, y},
         Renderable {
             glyph: rltk::to_cp437('*'),
@@ -171,6 +224,7 @@ fn fireball_scroll(world: &mut World, x: i32, y: i32) {
 
 fn confusion_scroll(world: &mut World, x: i32, y: i32) {
     world.spawn((
+        SerializeMe {},
         Position {x, y},
         Renderable {
             glyph: rltk::to_cp437('&'),
@@ -189,6 +243,7 @@ fn confusion_scroll(world: &mut World, x: i32, y: i32) {
 
 fn dagger(world: &mut World, x: i32, y: i32) {
     world.spawn((
+        SerializeMe {},
         Position {x, y},
         Renderable {
             glyph: rltk::to_cp437('│'),
@@ -206,6 +261,7 @@ fn dagger(world: &mut World, x: i32, y: i32) {
 
 fn longsword(world: &mut World, x: i32, y: i32) {
     world.spawn((
+        SerializeMe {},
         Position {x, y},
         Renderable {
             glyph: rltk::to_cp437('│'),
@@ -223,6 +279,7 @@ fn longsword(world: &mut World, x: i32, y: i32) {
 
 fn shield(world: &mut World, x: i32, y: i32) {
     world.spawn((
+        SerializeMe {},
         Position {x, y},
         Renderable {
             glyph: rltk::to_cp437('°'),
@@ -240,6 +297,7 @@ fn shield(world: &mut World, x: i32, y: i32) {
 
 fn tower_shield(world: &mut World, x: i32, y: i32) {
     world.spawn((
+        SerializeMe {},
         Position {x, y},
         Renderable {
             glyph: rltk::to_cp437('°'),
@@ -254,3 +312,33 @@ fn tower_shield(world: &mut World, x: i32, y: i32) {
         MeleeDefenseBonus {defense: 8}
     ));
 }
+
+fn door(world: &mut World, x: i32, y: i32) {
+    world.spawn((
+        SerializeMe {},
+        Position {x, y},
+        Renderable {
+            glyph: rltk::to_cp437('+'),
+            fg: Palette::COLOR_3,
+            bg: Palette::MAIN_BG,
+            order: RenderOrder::Items,
+            ..Default::default()
+        },
+        Name {name: "Door".to_string()},
+        Door {open: false}
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_spawnable_name_spawns_an_entity() {
+        for name in SPAWNABLE_NAMES {
+            let mut world = World::new();
+            spawn_entity(&mut world, (1, 1), name);
+            assert_eq!(world.len(), 1, "{} spawned nothing", name);
+        }
+    }
+}
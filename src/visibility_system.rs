@@ -1,6 +1,5 @@
 use hecs::*;
 use resources::Resources;
-use rltk;
 use rltk::{Point};
 use crate::map::{Map};
 use crate::components::{Position, Viewshed, Player};
@@ -32,7 +32,7 @@ impl WeightedTable {
     }
 
     pub fn roll(&self, rng: &mut RandomNumberGenerator) -> Option<String> {
-        if self.entries.len() == 0 { return None }
+        if self.entries.is_empty() { return None }
 
         let roll = rng.range(0, self.total_weight);
         let mut running_weight = 0;